@@ -0,0 +1,418 @@
+//! Keyframe animation of scene properties: object transforms and materials, the light and the
+//! camera. Clips are played by the renderer's `Animations`, which advances them by each frame's
+//! time and writes the sampled values before the app's `update`.
+
+use std::collections::{HashMap, BTreeMap};
+use std::sync::Arc;
+
+use cgmath::{Matrix3, Matrix4, Vector3, Quaternion, Euler, Deg, InnerSpace, One};
+
+use td::{Vec3, Color, Camera, Light, Projection};
+use obj::Object;
+use view::View;
+use skin::{Trs, nlerp};
+
+/// Values that keyframes can hold
+pub trait Animatable: Copy {
+	fn lerp(self, other: Self, t: f32) -> Self;
+	
+	/// Hermite spline through `b` and `c` at `t`, shaped by the keys `a` before and `d` after.
+	/// The tangents at `b` and `c` are `(c - a) * scale[0]` and `(d - b) * scale[1]`, which makes
+	/// it a Catmull-Rom spline when both are 0.5.
+	fn cubic(a: Self, b: Self, c: Self, d: Self, t: f32, scale: [f32; 2]) -> Self;
+}
+
+fn hermite(a: f32, b: f32, c: f32, d: f32, t: f32, scale: [f32; 2]) -> f32 {
+	let (t2, t3) = (t * t, t * t * t);
+	let (mb, mc) = ((c - a) * scale[0], (d - b) * scale[1]);
+	(2.0 * t3 - 3.0 * t2 + 1.0) * b
+		+ (t3 - 2.0 * t2 + t) * mb
+		+ (3.0 * t2 - 2.0 * t3) * c
+		+ (t3 - t2) * mc
+}
+
+impl Animatable for f32 {
+	fn lerp(self, other: f32, t: f32) -> f32 {
+		self + (other - self) * t
+	}
+	
+	fn cubic(a: f32, b: f32, c: f32, d: f32, t: f32, scale: [f32; 2]) -> f32 {
+		hermite(a, b, c, d, t, scale)
+	}
+}
+
+impl Animatable for Vec3 {
+	fn lerp(self, other: Vec3, t: f32) -> Vec3 {
+		self + (other - self) * t
+	}
+	
+	fn cubic(a: Vec3, b: Vec3, c: Vec3, d: Vec3, t: f32, scale: [f32; 2]) -> Vec3 {
+		Vector3::new(
+			hermite(a.x, b.x, c.x, d.x, t, scale),
+			hermite(a.y, b.y, c.y, d.y, t, scale),
+			hermite(a.z, b.z, c.z, d.z, t, scale),
+		)
+	}
+}
+
+impl Animatable for Color {
+	fn lerp(self, other: Color, t: f32) -> Color {
+		let (a, b) = (self.raw(), other.raw());
+		Color::new(a[0].lerp(b[0], t), a[1].lerp(b[1], t), a[2].lerp(b[2], t), a[3].lerp(b[3], t))
+	}
+	
+	fn cubic(a: Color, b: Color, c: Color, d: Color, t: f32, scale: [f32; 2]) -> Color {
+		let (a, b, c, d) = (a.raw(), b.raw(), c.raw(), d.raw());
+		let channel = |i: usize| hermite(a[i], b[i], c[i], d[i], t, scale).max(0.0);
+		Color::new(channel(0), channel(1), channel(2), channel(3))
+	}
+}
+
+/// Rotations take the shorter way around. Splines don't overshoot for rotations, they ease
+/// between the two keys instead.
+impl Animatable for Quaternion<f32> {
+	fn lerp(self, other: Quaternion<f32>, t: f32) -> Quaternion<f32> {
+		nlerp(self, other, t)
+	}
+	
+	fn cubic(_: Quaternion<f32>, b: Quaternion<f32>, c: Quaternion<f32>, _: Quaternion<f32>, t: f32, _: [f32; 2]) -> Quaternion<f32> {
+		nlerp(b, c, Easing::CubicInOut.apply(t))
+	}
+}
+
+/// How values are filled in between two keys
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+	/// Holds each key's value until the next key
+	Step,
+	Linear,
+	/// A smooth curve through every key. The slope at each key comes from its neighbours and the
+	/// time between them, so unevenly spaced keys don't overshoot. Rotations aren't a spline: they
+	/// ease in and out between each pair of keys, which stops at every key.
+	CubicSpline,
+}
+
+/// Reshapes the progress from one key to the next, from 0 to 1
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Easing {
+	Linear,
+	QuadIn,
+	QuadOut,
+	QuadInOut,
+	CubicIn,
+	CubicOut,
+	CubicInOut,
+	SineInOut,
+}
+
+impl Easing {
+	pub fn apply(self, t: f32) -> f32 {
+		let t = t.max(0.0).min(1.0);
+		match self {
+			Easing::Linear => t,
+			Easing::QuadIn => t * t,
+			Easing::QuadOut => t * (2.0 - t),
+			Easing::QuadInOut => if t < 0.5 { 2.0 * t * t } else { 1.0 - 2.0 * (1.0 - t) * (1.0 - t) },
+			Easing::CubicIn => t * t * t,
+			Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+			Easing::CubicInOut => if t < 0.5 { 4.0 * t * t * t } else { 1.0 - 4.0 * (1.0 - t).powi(3) },
+			Easing::SineInOut => 0.5 - 0.5 * (t * ::std::f32::consts::PI).cos(),
+		}
+	}
+}
+
+impl Default for Easing {
+	fn default() -> Self {
+		Easing::Linear
+	}
+}
+
+/// Values at points in time, in seconds, sorted by time. Also used for the joints of skeletal
+/// animation, see `skin::Channel`.
+#[derive(Clone, Debug)]
+pub struct Curve<T> {
+	pub times: Vec<f32>,
+	pub values: Vec<T>,
+	pub interpolation: Interpolation,
+	/// Applied separately between each pair of keys
+	pub easing: Easing,
+}
+
+impl<T: Animatable> Curve<T> {
+	pub fn new(mut keys: Vec<(f32, T)>, interpolation: Interpolation) -> Self {
+		keys.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+		Curve {
+			times: keys.iter().map(|k| k.0).collect(),
+			values: keys.iter().map(|k| k.1).collect(),
+			interpolation,
+			easing: Easing::Linear,
+		}
+	}
+	
+	pub fn linear(keys: Vec<(f32, T)>) -> Self {
+		Curve::new(keys, Interpolation::Linear)
+	}
+	
+	pub fn step(keys: Vec<(f32, T)>) -> Self {
+		Curve::new(keys, Interpolation::Step)
+	}
+	
+	/// A spline through the keys, see `Interpolation::CubicSpline` for what that means for rotations
+	pub fn cubic(keys: Vec<(f32, T)>) -> Self {
+		Curve::new(keys, Interpolation::CubicSpline)
+	}
+	
+	pub fn with_easing(mut self, easing: Easing) -> Self {
+		self.easing = easing;
+		self
+	}
+	
+	/// The value at `time`, holding the first and last values outside of the keys
+	pub fn sample(&self, time: f32) -> Option<T> {
+		let last = self.times.len().checked_sub(1)?;
+		if time <= self.times[0] {
+			return Some(self.values[0]);
+		}
+		if time >= self.times[last] {
+			return Some(self.values[last]);
+		}
+		let next = self.times.iter().position(|&t| t > time).unwrap_or(last);
+		let (t0, t1) = (self.times[next - 1], self.times[next]);
+		let t = if t1 > t0 { (time - t0) / (t1 - t0) } else { 0.0 };
+		let t = self.easing.apply(t);
+		let (b, c) = (self.values[next - 1], self.values[next]);
+		Some(match self.interpolation {
+			Interpolation::Step => b,
+			Interpolation::Linear => b.lerp(c, t),
+			Interpolation::CubicSpline => {
+				// The ends act as if the first and last keys were repeated
+				let (before, after) = (next.saturating_sub(2), (next + 1).min(last));
+				let (a, d) = (self.values[before], self.values[after]);
+				// Tangents are per second, scaled to the length of this segment
+				let scale = [(t1 - t0) / (t1 - self.times[before]), (t1 - t0) / (self.times[after] - t0)];
+				T::cubic(a, b, c, d, t, scale)
+			},
+		})
+	}
+	
+	pub fn end(&self) -> f32 {
+		self.times.last().cloned().unwrap_or(0.0)
+	}
+}
+
+/// One animated property. Objects are looked up by their name in the renderer's targets and
+/// skipped if there isn't one. Camera tracks move the app's camera, or with a view index the
+/// camera of that view, skipped if there isn't one.
+#[derive(Clone, Debug)]
+pub enum Track {
+	/// Replaces the translation of the object's transform, keeping its rotation and scale
+	Translation(String, Curve<Vec3>),
+	Rotation(String, Curve<Quaternion<f32>>),
+	Scale(String, Curve<Vec3>),
+	Color(String, Curve<Color>),
+	PointSize(String, Curve<f32>),
+	LineWidth(String, Curve<f32>),
+	/// The weight of one of the object's morph targets, by index
+	MorphWeight(String, usize, Curve<f32>),
+	LightPosition(Curve<Vec3>),
+	LightColor(Curve<Color>),
+	LightAmbient(Curve<f32>),
+	CameraPosition(Option<usize>, Curve<Vec3>),
+	/// Pitch, yaw and roll in degrees, the same as `Camera::set_rot`
+	CameraRotation(Option<usize>, Curve<Vec3>),
+	/// Vertical field of view in degrees, for perspective projections
+	CameraFov(Option<usize>, Curve<f32>),
+}
+
+impl Track {
+	fn end(&self) -> f32 {
+		match *self {
+			Track::Translation(_, ref c) | Track::Scale(_, ref c) => c.end(),
+			Track::Rotation(_, ref c) => c.end(),
+			Track::Color(_, ref c) => c.end(),
+			Track::PointSize(_, ref c) | Track::LineWidth(_, ref c) | Track::MorphWeight(_, _, ref c) => c.end(),
+			Track::LightPosition(ref c) | Track::CameraPosition(_, ref c) | Track::CameraRotation(_, ref c) => c.end(),
+			Track::LightColor(ref c) => c.end(),
+			Track::LightAmbient(ref c) | Track::CameraFov(_, ref c) => c.end(),
+		}
+	}
+	
+	fn apply(&self, time: f32, targets: &mut HashMap<String, Object>, camera: &mut Camera, views: &mut [View], light: &mut Light) -> Option<()> {
+		match *self {
+			Track::Translation(ref name, ref c) => {
+				let object = targets.get_mut(name)?;
+				let mut trs = decompose(&object.transform);
+				trs.translation = c.sample(time)?;
+				object.transform = trs.matrix();
+			},
+			Track::Rotation(ref name, ref c) => {
+				let object = targets.get_mut(name)?;
+				let mut trs = decompose(&object.transform);
+				trs.rotation = c.sample(time)?;
+				object.transform = trs.matrix();
+			},
+			Track::Scale(ref name, ref c) => {
+				let object = targets.get_mut(name)?;
+				let mut trs = decompose(&object.transform);
+				trs.scale = c.sample(time)?;
+				object.transform = trs.matrix();
+			},
+			Track::Color(ref name, ref c) => targets.get_mut(name)?.material.color = c.sample(time)?,
+			Track::PointSize(ref name, ref c) => targets.get_mut(name)?.material.point_size = c.sample(time)?,
+			Track::LineWidth(ref name, ref c) => targets.get_mut(name)?.material.line_width = c.sample(time)?,
+			Track::MorphWeight(ref name, index, ref c) => {
+				let object = targets.get_mut(name)?;
+				if object.morph_weights.len() <= index {
+					object.morph_weights.resize(index + 1, 0.0);
+				}
+				object.morph_weights[index] = c.sample(time)?;
+			},
+			Track::LightPosition(ref c) => light.position = c.sample(time)?,
+			Track::LightColor(ref c) => light.color = c.sample(time)?,
+			Track::LightAmbient(ref c) => light.ambient = c.sample(time)?,
+			Track::CameraPosition(view, ref c) => camera_of(camera, views, view)?.set_pos(c.sample(time)?),
+			Track::CameraRotation(view, ref c) => {
+				let r = c.sample(time)?;
+				camera_of(camera, views, view)?.set_rot(Euler::new(Deg(r.x), Deg(r.y), Deg(r.z)));
+			},
+			Track::CameraFov(view, ref c) => if let Projection::Perspective(ref mut p) = camera_of(camera, views, view)?.proj {
+				p.fovy = Deg(c.sample(time)?.max(1e-3).min(179.0)).into();
+			},
+		}
+		Some(())
+	}
+}
+
+/// The camera a camera track moves: the app's, or that of the view at `view`
+fn camera_of<'a>(camera: &'a mut Camera, views: &'a mut [View], view: Option<usize>) -> Option<&'a mut Camera> {
+	match view {
+		Some(index) => views.get_mut(index).map(|view| &mut view.camera),
+		None => Some(camera),
+	}
+}
+
+/// Splits a transform made of a translation, rotation and scale back into them. Shear is lost.
+pub(crate) fn decompose(m: &Matrix4<f32>) -> Trs {
+	let (x, y, z) = (m.x.truncate(), m.y.truncate(), m.z.truncate());
+	let scale = Vector3::new(x.magnitude(), y.magnitude(), z.magnitude());
+	let rotation = if scale.x > 0.0 && scale.y > 0.0 && scale.z > 0.0 {
+		Quaternion::from(Matrix3::from_cols(x / scale.x, y / scale.y, z / scale.z)).normalize()
+	} else {
+		Quaternion::one()
+	};
+	Trs { translation: m.w.truncate(), rotation, scale }
+}
+
+#[derive(Clone, Debug)]
+pub struct Clip {
+	pub name: String,
+	/// Length in seconds, which is when the last key of any track is
+	pub duration: f32,
+	pub tracks: Vec<Track>,
+}
+
+impl Clip {
+	pub fn new<S: Into<String>>(name: S, tracks: Vec<Track>) -> Self {
+		let duration = tracks.iter().map(Track::end).fold(0.0, f32::max);
+		Clip { name: name.into(), duration, tracks }
+	}
+}
+
+/// A clip being played by `Animations`
+#[derive(Clone, Debug)]
+pub struct Player {
+	pub clip: Arc<Clip>,
+	/// Position in the clip in seconds
+	pub time: f32,
+	/// Multiplier for how fast time passes in the clip, negative to play it backwards
+	pub speed: f32,
+	pub looping: bool,
+	pub paused: bool,
+}
+
+impl Player {
+	/// Whether a clip that doesn't loop has reached its end, where it holds its last values
+	pub fn is_finished(&self) -> bool {
+		!self.looping && if self.speed < 0.0 { self.time <= 0.0 } else { self.time >= self.clip.duration }
+	}
+}
+
+/// The clips playing in a renderer, by name. They're advanced and applied each frame after the
+/// events are handled and before the app's `update`, so the app can still override what they set.
+#[derive(Clone, Debug, Default)]
+pub struct Animations {
+	players: BTreeMap<String, Player>,
+}
+
+impl Animations {
+	pub fn new() -> Self {
+		Animations::default()
+	}
+	
+	/// Starts playing a clip from the beginning once, replacing whatever played under `name`
+	pub fn play<S: Into<String>>(&mut self, name: S, clip: Arc<Clip>) -> &mut Player {
+		let name = name.into();
+		self.players.insert(name.clone(), Player {
+			clip,
+			time: 0.0,
+			speed: 1.0,
+			looping: false,
+			paused: false,
+		});
+		self.players.get_mut(&name).unwrap()
+	}
+	
+	/// Starts playing a clip that starts over whenever it ends
+	pub fn play_looping<S: Into<String>>(&mut self, name: S, clip: Arc<Clip>) -> &mut Player {
+		let player = self.play(name, clip);
+		player.looping = true;
+		player
+	}
+	
+	pub fn get(&self, name: &str) -> Option<&Player> {
+		self.players.get(name)
+	}
+	
+	pub fn get_mut(&mut self, name: &str) -> Option<&mut Player> {
+		self.players.get_mut(name)
+	}
+	
+	/// Stops a clip, leaving everything it animated as it was last set
+	pub fn stop(&mut self, name: &str) -> Option<Player> {
+		self.players.remove(name)
+	}
+	
+	pub fn is_playing(&self, name: &str) -> bool {
+		self.players.get(name).map_or(false, |p| !p.paused && !p.is_finished())
+	}
+	
+	/// Moves every clip that isn't paused forward by `ms` milliseconds
+	pub fn advance(&mut self, ms: f32) {
+		let seconds = ms / 1000.0;
+		for player in self.players.values_mut() {
+			if player.paused {
+				continue;
+			}
+			player.time += seconds * player.speed;
+			let duration = player.clip.duration;
+			if player.looping && duration > 0.0 {
+				player.time %= duration;
+				if player.time < 0.0 {
+					player.time += duration;
+				}
+			} else {
+				player.time = player.time.max(0.0).min(duration);
+			}
+		}
+	}
+	
+	/// Writes every clip's values at its current time. Clips are applied in order of their names,
+	/// so where two animate the same property the one whose name sorts last wins.
+	pub fn apply(&self, targets: &mut HashMap<String, Object>, camera: &mut Camera, views: &mut [View], light: &mut Light) {
+		for player in self.players.values() {
+			for track in &player.clip.tracks {
+				track.apply(player.time, targets, camera, views, light);
+			}
+		}
+	}
+}
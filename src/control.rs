@@ -0,0 +1,401 @@
+//! Camera controllers that turn window events into camera movement. Pass every event to the
+//! controller's `handle_event` from `App::handle_event`, and call its `update` with the camera from
+//! `App::update`.
+
+use cgmath::{Vector3, Euler, Deg, InnerSpace, Zero};
+use winit::{Event, WindowEvent, DeviceEvent, ElementState, MouseButton, MouseScrollDelta, KeyboardInput, VirtualKeyCode};
+
+use td::{Vec3, Camera, euler_direction, direction_euler};
+
+/// How far to move towards a goal in `seconds` to close the gap at `rate` per second, independent
+/// of the frame rate. A rate of zero jumps straight to the goal.
+fn smoothing(rate: f32, seconds: f32) -> f32 {
+	if rate <= 0.0 { 1.0 } else { 1.0 - (-rate * seconds).exp() }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Orbit {
+	target: Vec3,
+	yaw: f32,
+	pitch: f32,
+	distance: f32,
+}
+
+/// Turntable navigation around a target point: dragging with the left mouse button rotates,
+/// scrolling zooms and dragging with the middle button pans. The public `target`, `yaw`, `pitch`
+/// and `distance` are where the camera is heading, and it eases towards them by `damping`.
+#[derive(Clone, Debug)]
+pub struct OrbitController {
+	pub target: Vec3,
+	/// Degrees around the camera's world up axis, with the same meaning as its yaw
+	pub yaw: f32,
+	/// Degrees the camera looks up, so it sits below the target when this is positive
+	pub pitch: f32,
+	pub distance: f32,
+	pub min_distance: f32,
+	pub max_distance: f32,
+	pub min_pitch: f32,
+	pub max_pitch: f32,
+	/// Degrees of rotation per pixel dragged
+	pub rotate_speed: f32,
+	/// Fraction of the distance zoomed per line scrolled
+	pub zoom_speed: f32,
+	/// Fraction of the distance panned per pixel dragged
+	pub pan_speed: f32,
+	/// How quickly the camera catches up with the controls, per second. Zero disables smoothing.
+	pub damping: f32,
+	/// How quickly rotation that carries on after the mouse is released slows down, per second.
+	/// Zero disables inertia.
+	pub friction: f32,
+	current: Orbit,
+	rotating: bool,
+	panning: bool,
+	cursor: Option<(f64, f64)>,
+	/// Rotation dragged since the last update, and how fast the drag was going in degrees per second
+	dragged: (f32, f32),
+	velocity: (f32, f32),
+	/// The camera's world up as of the last update
+	world_up: Vec3,
+}
+
+impl OrbitController {
+	/// Looks at `target` from `distance` away along positive z
+	pub fn new(target: Vec3, distance: f32) -> Self {
+		let current = Orbit { target, yaw: -90.0, pitch: 0.0, distance };
+		OrbitController {
+			target,
+			yaw: current.yaw,
+			pitch: current.pitch,
+			distance,
+			min_distance: 0.01,
+			max_distance: 10000.0,
+			min_pitch: -89.0,
+			max_pitch: 89.0,
+			rotate_speed: 0.3,
+			zoom_speed: 0.1,
+			pan_speed: 0.002,
+			damping: 15.0,
+			friction: 5.0,
+			current,
+			rotating: false,
+			panning: false,
+			cursor: None,
+			dragged: (0.0, 0.0),
+			velocity: (0.0, 0.0),
+			world_up: Vector3::unit_y(),
+		}
+	}
+	
+	/// Orbits `target` from wherever the camera currently is
+	pub fn from_camera(camera: &Camera, target: Vec3) -> Self {
+		let offset = target - camera.get_pos();
+		let distance = offset.magnitude();
+		let mut orbit = OrbitController::new(target, distance);
+		orbit.world_up = camera.world_up();
+		if distance > 0.0 {
+			let (pitch, yaw) = direction_euler(orbit.world_up, offset / distance);
+			orbit.pitch = pitch;
+			orbit.yaw = yaw;
+		}
+		orbit.snap();
+		orbit
+	}
+	
+	/// Skips the smoothing, so the next update puts the camera right where the controls say
+	pub fn snap(&mut self) {
+		self.clamp();
+		self.current = self.goal();
+		self.velocity = (0.0, 0.0);
+	}
+	
+	/// Turns around the target by degrees of yaw and pitch
+	pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+		self.yaw += yaw;
+		self.pitch += pitch;
+		self.clamp();
+	}
+	
+	/// Moves closer to the target by `amount` steps of `zoom_speed`, or further away if negative
+	pub fn zoom(&mut self, amount: f32) {
+		self.distance *= (1.0 - self.zoom_speed).max(0.0).powf(amount);
+		self.clamp();
+	}
+	
+	/// Moves the target across the view, by pixels dragged right and down
+	pub fn pan(&mut self, dx: f32, dy: f32) {
+		let front = euler_direction(self.world_up, self.pitch, self.yaw);
+		let right = front.cross(self.world_up).normalize();
+		let up = right.cross(front).normalize();
+		let scale = self.pan_speed * self.distance;
+		self.target += (up * dy - right * dx) * scale;
+	}
+	
+	pub fn handle_event(&mut self, event: &Event) {
+		let event = match *event {
+			Event::WindowEvent { ref event, .. } => event,
+			_ => return,
+		};
+		match *event {
+			WindowEvent::MouseInput { state, button, .. } => {
+				let pressed = state == ElementState::Pressed;
+				match button {
+					MouseButton::Left => self.rotating = pressed,
+					MouseButton::Middle => self.panning = pressed,
+					_ => {},
+				}
+				if pressed {
+					self.velocity = (0.0, 0.0);
+				}
+			},
+			WindowEvent::CursorMoved { position, .. } => {
+				if let Some(last) = self.cursor {
+					let (dx, dy) = ((position.0 - last.0) as f32, (position.1 - last.1) as f32);
+					if self.rotating {
+						let (yaw, pitch) = (dx * self.rotate_speed, -dy * self.rotate_speed);
+						self.rotate(yaw, pitch);
+						self.dragged.0 += yaw;
+						self.dragged.1 += pitch;
+					} else if self.panning {
+						self.pan(dx, dy);
+					}
+				}
+				self.cursor = Some(position);
+			},
+			WindowEvent::CursorLeft { .. } => self.cursor = None,
+			WindowEvent::MouseWheel { delta, .. } => match delta {
+				MouseScrollDelta::LineDelta(_, y) => self.zoom(y),
+				// Roughly how far one line of a wheel scrolls on touchpads
+				MouseScrollDelta::PixelDelta(_, y) => self.zoom(y / 20.0),
+			},
+			_ => {},
+		}
+	}
+	
+	/// Eases the camera towards the controls and keeps it pointed at the target
+	pub fn update(&mut self, ms: f32, camera: &mut Camera) {
+		let seconds = ms / 1000.0;
+		if self.rotating {
+			if seconds > 0.0 {
+				self.velocity = (self.dragged.0 / seconds, self.dragged.1 / seconds);
+			}
+		} else if self.friction > 0.0 {
+			let (yaw, pitch) = self.velocity;
+			self.rotate(yaw * seconds, pitch * seconds);
+			let decay = (-self.friction * seconds).exp();
+			self.velocity = (yaw * decay, pitch * decay);
+			if self.velocity.0.abs() < 0.01 && self.velocity.1.abs() < 0.01 {
+				self.velocity = (0.0, 0.0);
+			}
+		}
+		self.dragged = (0.0, 0.0);
+		
+		let goal = self.goal();
+		let t = smoothing(self.damping, seconds);
+		self.current = Orbit {
+			target: self.current.target + (goal.target - self.current.target) * t,
+			yaw: self.current.yaw + (goal.yaw - self.current.yaw) * t,
+			pitch: self.current.pitch + (goal.pitch - self.current.pitch) * t,
+			// Zooming eases by ratio so it feels the same near and far
+			distance: self.current.distance * (goal.distance / self.current.distance).powf(t),
+		};
+		if !self.current.distance.is_finite() || self.current.distance <= 0.0 {
+			self.current.distance = goal.distance;
+		}
+		
+		let Orbit { target, yaw, pitch, distance } = self.current;
+		let roll = camera.get_rot().z;
+		self.world_up = camera.world_up();
+		camera.set_pos(target - euler_direction(self.world_up, pitch, yaw) * distance);
+		camera.set_rot(Euler::new(Deg(pitch), Deg(yaw), roll));
+	}
+	
+	fn goal(&self) -> Orbit {
+		Orbit { target: self.target, yaw: self.yaw, pitch: self.pitch, distance: self.distance }
+	}
+	
+	fn clamp(&mut self) {
+		self.pitch = self.pitch.max(self.min_pitch).min(self.max_pitch);
+		self.distance = self.distance.max(self.min_distance).min(self.max_distance);
+	}
+}
+
+/// The keys a `FlyController` moves with
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FlyKeys {
+	pub forward: VirtualKeyCode,
+	pub back: VirtualKeyCode,
+	pub left: VirtualKeyCode,
+	pub right: VirtualKeyCode,
+	pub up: VirtualKeyCode,
+	pub down: VirtualKeyCode,
+	/// Held to move `sprint` times faster
+	pub sprint: VirtualKeyCode,
+}
+
+impl Default for FlyKeys {
+	fn default() -> Self {
+		FlyKeys {
+			forward: VirtualKeyCode::W,
+			back: VirtualKeyCode::S,
+			left: VirtualKeyCode::A,
+			right: VirtualKeyCode::D,
+			up: VirtualKeyCode::Space,
+			down: VirtualKeyCode::LControl,
+			sprint: VirtualKeyCode::LShift,
+		}
+	}
+}
+
+/// First person navigation: the mouse turns the camera and the keys move it, by the same distance
+/// per second at any frame rate
+#[derive(Clone, Debug)]
+pub struct FlyController {
+	pub keys: FlyKeys,
+	/// Units per second
+	pub speed: f32,
+	/// Multiplies the speed while the sprint key is held
+	pub sprint: f32,
+	/// How quickly the camera gets up to speed and stops again, per second. Zero starts and stops
+	/// instantly.
+	pub acceleration: f32,
+	/// Degrees turned per pixel the mouse moves
+	pub sensitivity: f32,
+	pub invert_y: bool,
+	/// Degrees around the camera's world up axis, with the same meaning as its yaw. The camera is
+	/// turned to this and `pitch` whenever the controller turns it.
+	pub yaw: f32,
+	/// Degrees the camera looks up
+	pub pitch: f32,
+	pub min_pitch: f32,
+	pub max_pitch: f32,
+	/// Whether moving forwards and sideways stays level, like walking, instead of following where
+	/// the camera looks
+	pub level: bool,
+	/// Only turns while this button is held, or always if `None`, which suits a grabbed cursor
+	pub look_button: Option<MouseButton>,
+	/// Forward, back, left, right, up, down and sprint
+	held: [bool; 7],
+	looking: bool,
+	turn: (f32, f32),
+	velocity: Vec3,
+}
+
+impl FlyController {
+	/// Looks level along the world's reference direction, the same way a new `Camera` does
+	pub fn new(speed: f32) -> Self {
+		FlyController {
+			keys: FlyKeys::default(),
+			speed,
+			sprint: 3.0,
+			acceleration: 10.0,
+			sensitivity: 0.1,
+			invert_y: false,
+			yaw: 0.0,
+			pitch: 0.0,
+			min_pitch: -89.99,
+			max_pitch: 89.99,
+			level: true,
+			look_button: None,
+			held: [false; 7],
+			looking: false,
+			turn: (0.0, 0.0),
+			velocity: Vector3::zero(),
+		}
+	}
+	
+	/// Carries on from wherever the camera currently looks
+	pub fn from_camera(camera: &Camera, speed: f32) -> Self {
+		let mut fly = FlyController::new(speed);
+		let rot = camera.get_rot();
+		fly.yaw = rot.y.0;
+		fly.pitch = rot.x.0.max(fly.min_pitch).min(fly.max_pitch);
+		fly
+	}
+	
+	/// Turns by degrees of yaw and pitch on the next update
+	pub fn look(&mut self, yaw: f32, pitch: f32) {
+		self.turn.0 += yaw;
+		self.turn.1 += pitch;
+	}
+	
+	/// Presses or releases a key the same way a keyboard event would
+	pub fn set_key(&mut self, key: VirtualKeyCode, pressed: bool) {
+		let k = self.keys;
+		let index = [k.forward, k.back, k.left, k.right, k.up, k.down, k.sprint].iter().position(|&c| c == key);
+		if let Some(i) = index {
+			self.held[i] = pressed;
+		}
+	}
+	
+	pub fn velocity(&self) -> Vec3 {
+		self.velocity
+	}
+	
+	pub fn handle_event(&mut self, event: &Event) {
+		match *event {
+			Event::WindowEvent { ref event, .. } => match *event {
+				WindowEvent::KeyboardInput { input: KeyboardInput { state, virtual_keycode: Some(key), .. }, .. } => {
+					self.set_key(key, state == ElementState::Pressed);
+				},
+				WindowEvent::MouseInput { state, button, .. } => {
+					if Some(button) == self.look_button {
+						self.looking = state == ElementState::Pressed;
+					}
+				},
+				// Keys released while another window has focus would otherwise stay held
+				WindowEvent::Focused(false) => {
+					self.held = [false; 7];
+					self.looking = false;
+				},
+				_ => {},
+			},
+			Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
+				if self.look_button.is_none() || self.looking {
+					let dy = if self.invert_y { delta.1 } else { -delta.1 };
+					self.look(delta.0 as f32 * self.sensitivity, dy as f32 * self.sensitivity);
+				}
+			},
+			_ => {},
+		}
+	}
+	
+	/// Turns and moves the camera by what happened since the last update
+	pub fn update(&mut self, ms: f32, camera: &mut Camera) {
+		let seconds = ms / 1000.0;
+		// The camera is left alone unless it turns, so the app can still turn it some other way
+		if self.turn != (0.0, 0.0) {
+			self.pitch = (self.pitch + self.turn.1).max(self.min_pitch).min(self.max_pitch);
+			self.yaw = (self.yaw + self.turn.0) % 360.0;
+			camera.set_rot(Euler::new(Deg(self.pitch), Deg(self.yaw), Deg(0.0)));
+			self.turn = (0.0, 0.0);
+		}
+		
+		let up = camera.world_up();
+		let (front, right) = if self.level {
+			let front = euler_direction(up, 0.0, self.yaw);
+			(front, front.cross(up).normalize())
+		} else {
+			let (front, right, _) = camera.get_vec();
+			(front, right)
+		};
+		let held = self.held;
+		let axis = |positive: usize, negative: usize| (held[positive] as i32 - held[negative] as i32) as f32;
+		let mut wish = front * axis(0, 1) + right * axis(3, 2) + up * axis(4, 5);
+		if wish.magnitude2() > 0.0 {
+			let speed = if self.held[6] { self.speed * self.sprint } else { self.speed };
+			wish = wish.normalize() * speed;
+		}
+		
+		self.velocity += (wish - self.velocity) * smoothing(self.acceleration, seconds);
+		if self.velocity.magnitude2() > 0.0 {
+			let pos = camera.get_pos();
+			camera.set_pos(pos + self.velocity * seconds);
+		}
+	}
+}
+
+impl Default for FlyController {
+	fn default() -> Self {
+		FlyController::new(5.0)
+	}
+}
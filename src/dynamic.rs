@@ -0,0 +1,466 @@
+//! Meshes whose vertices and indices can change after they're created.
+//!
+//! A `DynamicMesh` keeps a copy of its data on the CPU and a small ring of host-visible buffers.
+//! Updates only touch the CPU copy and mark ranges dirty; when the mesh is drawn, the next buffer
+//! the GPU isn't reading from gets the ranges it's missing copied into it. A new buffer is added
+//! to the ring whenever all of them are still in use.
+//!
+//! Like `Mesh`, a dynamic mesh can be made of any vertex type with a registered pipeline and put
+//! together as any `Topology`.
+
+use std::sync::{Arc, Mutex};
+use std::ops::Range;
+use std::fmt;
+
+use vulkano::buffer::{CpuAccessibleBuffer, BufferUsage, BufferAccess, BufferSlice, TypedBufferAccess};
+use vulkano::device::Device;
+
+use RenderInternal;
+use td::*;
+use obj::{Mesh, Topology};
+
+/// A handle to geometry that can be updated between frames. Clones refer to the same mesh, so one
+/// can be kept for updates while `mesh` hands out a `Mesh` to put in an `Object`.
+pub struct DynamicMesh<V: MeshVertex = Vertex> {
+	inner: Arc<Mutex<Inner<V>>>,
+}
+
+impl<V: MeshVertex> Clone for DynamicMesh<V> {
+	fn clone(&self) -> Self {
+		DynamicMesh {
+			inner: self.inner.clone(),
+		}
+	}
+}
+
+impl<V: MeshVertex> fmt::Debug for DynamicMesh<V> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let inner = self.inner.lock().unwrap();
+		let geometry = &inner.geometry;
+		write!(f, "DynamicMesh {{ verts: {}, indices: {:?}, topology: {:?}, buffers: {} }}",
+			geometry.verts.len(), geometry.indices.as_ref().map(|i| i.len()), geometry.topology, inner.slots.len())
+	}
+}
+
+struct Inner<V: MeshVertex> {
+	device: Arc<Device>,
+	geometry: Geometry<V>,
+	/// The bounds of the vertices as of the last frame, or `None` once they've changed since
+	bounds: Option<BoundingSphere>,
+	slots: Vec<Slot<V>>,
+	next: usize,
+}
+
+/// The CPU copy of a dynamic mesh and how much its buffers hold. Updates are checked and applied
+/// here, and return the range they changed for the buffers to catch up on.
+pub(crate) struct Geometry<V> {
+	pub verts: Vec<V>,
+	pub indices: Option<Vec<u32>>,
+	pub topology: Topology,
+	pub vert_capacity: usize,
+	pub index_capacity: usize,
+}
+
+struct Slot<V: MeshVertex> {
+	verts: Arc<CpuAccessibleBuffer<[V]>>,
+	indices: Option<Arc<CpuAccessibleBuffer<[u32]>>>,
+	/// What has changed since this buffer was last written to
+	dirty_verts: Option<Range<usize>>,
+	dirty_indices: Option<Range<usize>>,
+}
+
+/// The buffers to bind for one draw of a dynamic mesh
+pub(crate) struct Frame {
+	pub verts: Arc<BufferAccess + Send + Sync>,
+	pub indices: Option<BufferSlice<[u32], Arc<CpuAccessibleBuffer<[u32]>>>>,
+}
+
+/// What a `Mesh` needs from a dynamic mesh, whatever its vertex type
+pub(crate) trait DynamicBuffers: fmt::Debug + Send + Sync {
+	fn frame(&self) -> Option<Frame>;
+	fn bounds(&self) -> BoundingSphere;
+	fn vertex_count(&self) -> usize;
+	fn index_count(&self) -> Option<usize>;
+	fn joint_count(&self) -> usize;
+}
+
+impl<V: MeshVertex> DynamicMesh<V> {
+	/// Creates a triangle list drawn with an index buffer, like `Mesh::new`
+	pub fn new(internal: &RenderInternal, verts: Vec<V>, indices: Vec<u32>) -> Result<Self, ()> {
+		DynamicMesh::new_with_topology(internal, verts, indices, Topology::TriangleList)
+	}
+	
+	/// Like `new`, but the indices make up `topology` instead of a triangle list
+	pub fn new_with_topology(internal: &RenderInternal, verts: Vec<V>, indices: Vec<u32>, topology: Topology) -> Result<Self, ()> {
+		validate(&indices, verts.len(), topology)?;
+		Ok(DynamicMesh::create(internal, verts, Some(indices), topology))
+	}
+	
+	/// Creates a triangle list drawn without an index buffer, like `Mesh::new_pure`
+	pub fn new_pure(internal: &RenderInternal, verts: Vec<V>) -> Self {
+		DynamicMesh::create(internal, verts, None, Topology::TriangleList)
+	}
+	
+	/// Like `new_pure`, but the vertices make up `topology` instead of a triangle list
+	pub fn new_pure_with_topology(internal: &RenderInternal, verts: Vec<V>, topology: Topology) -> Result<Self, ()> {
+		if !topology.fits(verts.len()) {
+			return Err(())
+		}
+		Ok(DynamicMesh::create(internal, verts, None, topology))
+	}
+	
+	fn create(internal: &RenderInternal, verts: Vec<V>, indices: Option<Vec<u32>>, topology: Topology) -> Self {
+		let mut inner = Inner {
+			device: internal.device.clone(),
+			bounds: Some(BoundingSphere::from_verts(&verts)),
+			geometry: Geometry::new(verts, indices, topology),
+			slots: Vec::new(),
+			next: 0,
+		};
+		inner.slots.push(inner.new_slot());
+		DynamicMesh {
+			inner: Arc::new(Mutex::new(inner)),
+		}
+	}
+	
+	/// A mesh that draws whatever this one holds at the time, for use in an `Object`
+	pub fn mesh(&self) -> Mesh {
+		Mesh::from_dynamic(self.clone())
+	}
+	
+	pub fn topology(&self) -> Topology {
+		self.inner.lock().unwrap().geometry.topology
+	}
+	
+	pub fn vertex_count(&self) -> usize {
+		self.inner.lock().unwrap().geometry.verts.len()
+	}
+	
+	/// The number of indices, or `None` if the mesh isn't indexed
+	pub fn index_count(&self) -> Option<usize> {
+		self.inner.lock().unwrap().geometry.indices.as_ref().map(|i| i.len())
+	}
+	
+	/// How many vertices and indices fit in the current buffers before they have to grow
+	pub fn capacity(&self) -> (usize, usize) {
+		let inner = self.inner.lock().unwrap();
+		(inner.geometry.vert_capacity, inner.geometry.index_capacity)
+	}
+	
+	/// Grows the buffers so at least this many vertices and indices fit without reallocating
+	pub fn reserve(&self, verts: usize, indices: usize) {
+		let mut inner = self.inner.lock().unwrap();
+		inner.geometry.reserve(verts, indices);
+		inner.drop_outgrown();
+	}
+	
+	/// Replaces every vertex. Fails if an index would refer to a vertex that no longer exists, or
+	/// without indices if the vertices don't make up whole primitives.
+	pub fn set_verts(&self, verts: Vec<V>) -> Result<(), ()> {
+		let mut inner = self.inner.lock().unwrap();
+		let range = inner.geometry.set_verts(verts)?;
+		inner.mark_verts(range);
+		Ok(())
+	}
+	
+	/// Overwrites the vertices starting at `start`, appending any that go past the end. `start`
+	/// can't be past the current end, and without indices the vertices have to still make up
+	/// whole primitives.
+	pub fn update_verts(&self, start: usize, verts: &[V]) -> Result<(), ()> {
+		let mut inner = self.inner.lock().unwrap();
+		let range = inner.geometry.update_verts(start, verts)?;
+		inner.mark_verts(range);
+		Ok(())
+	}
+	
+	/// Replaces every index. Fails if the mesh isn't indexed or the indices don't make up whole
+	/// primitives of the mesh's topology.
+	pub fn set_indices(&self, indices: Vec<u32>) -> Result<(), ()> {
+		let mut inner = self.inner.lock().unwrap();
+		let range = inner.geometry.set_indices(indices)?;
+		inner.mark_indices(range);
+		Ok(())
+	}
+	
+	/// Overwrites the indices starting at `start`, appending any that go past the end. In a list,
+	/// whole primitives have to be written, starting on a primitive boundary.
+	pub fn update_indices(&self, start: usize, indices: &[u32]) -> Result<(), ()> {
+		let mut inner = self.inner.lock().unwrap();
+		let range = inner.geometry.update_indices(start, indices)?;
+		inner.mark_indices(range);
+		Ok(())
+	}
+	
+	/// Drops vertices past `verts` and indices past `indices`. Fails if a remaining index would
+	/// refer to a dropped vertex, or without indices if the remaining vertices don't make up
+	/// whole primitives.
+	pub fn truncate(&self, verts: usize, indices: usize) -> Result<(), ()> {
+		let mut inner = self.inner.lock().unwrap();
+		let count = inner.geometry.verts.len();
+		inner.geometry.truncate(verts, indices)?;
+		if verts < count {
+			inner.bounds = None;
+		}
+		Ok(())
+	}
+	
+	/// Calls `f` with the vertices and indices, e.g. to compute bounds or export the mesh
+	pub fn read<R, F: FnOnce(&[V], Option<&[u32]>) -> R>(&self, f: F) -> R {
+		let inner = self.inner.lock().unwrap();
+		f(&inner.geometry.verts, inner.geometry.indices.as_ref().map(|i| &i[..]))
+	}
+}
+
+impl<V: MeshVertex> DynamicBuffers for DynamicMesh<V> {
+	/// Brings a buffer the GPU isn't using up to date and returns it for drawing, or `None` if
+	/// there's nothing to draw
+	fn frame(&self) -> Option<Frame> {
+		self.inner.lock().unwrap().frame()
+	}
+	
+	/// The bounds of the vertices as of the last frame, so they're only worked out again once
+	/// per frame at most
+	fn bounds(&self) -> BoundingSphere {
+		let mut inner = self.inner.lock().unwrap();
+		inner.bounds()
+	}
+	
+	fn vertex_count(&self) -> usize {
+		DynamicMesh::vertex_count(self)
+	}
+	
+	fn index_count(&self) -> Option<usize> {
+		DynamicMesh::index_count(self)
+	}
+	
+	fn joint_count(&self) -> usize {
+		self.read(|verts, _| verts.iter().map(|v| v.joint_count()).max().unwrap_or(0))
+	}
+}
+
+impl<V: Copy> Geometry<V> {
+	/// Takes data that's already been checked, with room for exactly what's there
+	pub fn new(verts: Vec<V>, indices: Option<Vec<u32>>, topology: Topology) -> Self {
+		Geometry {
+			vert_capacity: verts.len().max(1),
+			index_capacity: indices.as_ref().map_or(0, |i| i.len().max(1)),
+			verts,
+			indices,
+			topology,
+		}
+	}
+	
+	/// Whether `count` vertices can be drawn, which without indices means whole primitives
+	fn fits(&self, count: usize) -> bool {
+		self.indices.is_some() || self.topology.fits(count)
+	}
+	
+	pub fn set_verts(&mut self, verts: Vec<V>) -> Result<Range<usize>, ()> {
+		match self.indices {
+			Some(ref indices) => validate(indices, verts.len(), self.topology)?,
+			None if !self.topology.fits(verts.len()) => return Err(()),
+			None => {},
+		}
+		let len = verts.len();
+		self.reserve(len, 0);
+		self.verts = verts;
+		Ok(0..len)
+	}
+	
+	pub fn update_verts(&mut self, start: usize, verts: &[V]) -> Result<Range<usize>, ()> {
+		let len = self.verts.len();
+		let end = start + verts.len();
+		if start > len || !self.fits(end.max(len)) {
+			return Err(());
+		}
+		self.reserve(end, 0);
+		let overlap = end.min(len);
+		self.verts[start..overlap].copy_from_slice(&verts[..overlap - start]);
+		self.verts.extend_from_slice(&verts[overlap - start..]);
+		Ok(start..end)
+	}
+	
+	pub fn set_indices(&mut self, indices: Vec<u32>) -> Result<Range<usize>, ()> {
+		if self.indices.is_none() {
+			return Err(());
+		}
+		validate(&indices, self.verts.len(), self.topology)?;
+		let len = indices.len();
+		self.reserve(0, len);
+		self.indices = Some(indices);
+		Ok(0..len)
+	}
+	
+	pub fn update_indices(&mut self, start: usize, indices: &[u32]) -> Result<Range<usize>, ()> {
+		let len = match self.indices {
+			Some(ref current) => current.len(),
+			None => return Err(()),
+		};
+		if start > len || !self.topology.fits(start) {
+			return Err(());
+		}
+		validate(indices, self.verts.len(), self.topology)?;
+		let end = start + indices.len();
+		self.reserve(0, end);
+		let current = self.indices.as_mut().unwrap();
+		let overlap = end.min(len);
+		current[start..overlap].copy_from_slice(&indices[..overlap - start]);
+		current.extend_from_slice(&indices[overlap - start..]);
+		Ok(start..end)
+	}
+	
+	pub fn truncate(&mut self, verts: usize, indices: usize) -> Result<(), ()> {
+		let verts = verts.min(self.verts.len());
+		match self.indices {
+			Some(ref current) => validate(&current[..indices.min(current.len())], verts, self.topology)?,
+			None if !self.topology.fits(verts) => return Err(()),
+			None => {},
+		}
+		self.verts.truncate(verts);
+		if let Some(ref mut current) = self.indices {
+			current.truncate(indices);
+		}
+		Ok(())
+	}
+	
+	/// Makes room for at least this many vertices and indices, growing geometrically so
+	/// appending a little at a time doesn't reallocate every frame
+	pub fn reserve(&mut self, verts: usize, indices: usize) {
+		if verts > self.vert_capacity {
+			self.vert_capacity = verts.next_power_of_two();
+		}
+		if self.indices.is_some() && indices > self.index_capacity {
+			self.index_capacity = indices.next_power_of_two();
+		}
+	}
+}
+
+impl<V: MeshVertex> Inner<V> {
+	fn new_slot(&self) -> Slot<V> {
+		// Only the part that's in use is ever drawn, so the rest is left uninitialized
+		let geometry = &self.geometry;
+		let verts = unsafe { CpuAccessibleBuffer::uninitialized_array(self.device.clone(), geometry.vert_capacity, BufferUsage::all()) }.unwrap();
+		verts.write().unwrap()[..geometry.verts.len()].copy_from_slice(&geometry.verts);
+		let indices = geometry.indices.as_ref().map(|indices| {
+			let mut indices = indices.clone();
+			indices.resize(geometry.index_capacity, 0);
+			CpuAccessibleBuffer::from_iter(self.device.clone(), BufferUsage::all(), indices.into_iter()).unwrap()
+		});
+		Slot {
+			verts,
+			indices,
+			dirty_verts: None,
+			dirty_indices: None,
+		}
+	}
+	
+	/// Forgets the buffers that are too small for the geometry's capacity. They may still be in
+	/// use, so they're left to be dropped once the GPU is done.
+	fn drop_outgrown(&mut self) {
+		let (verts, indices) = (self.geometry.vert_capacity, self.geometry.index_capacity);
+		let count = self.slots.len();
+		self.slots.retain(|slot| slot.verts.len() == verts && slot.indices.as_ref().map_or(0, |i| i.len()) == indices);
+		if self.slots.len() != count {
+			self.next = 0;
+		}
+	}
+	
+	fn bounds(&mut self) -> BoundingSphere {
+		if self.bounds.is_none() {
+			self.bounds = Some(BoundingSphere::from_verts(&self.geometry.verts));
+		}
+		self.bounds.unwrap()
+	}
+	
+	fn mark_verts(&mut self, range: Range<usize>) {
+		self.drop_outgrown();
+		self.bounds = None;
+		for slot in &mut self.slots {
+			slot.dirty_verts = Some(union(slot.dirty_verts.take(), range.clone()));
+		}
+	}
+	
+	fn mark_indices(&mut self, range: Range<usize>) {
+		self.drop_outgrown();
+		for slot in &mut self.slots {
+			slot.dirty_indices = Some(union(slot.dirty_indices.take(), range.clone()));
+		}
+	}
+	
+	fn frame(&mut self) -> Option<Frame> {
+		let index_count = self.geometry.indices.as_ref().map(|i| i.len());
+		if self.geometry.verts.is_empty() || index_count == Some(0) {
+			return None;
+		}
+		
+		let mut found = None;
+		for offset in 0..self.slots.len() {
+			let index = (self.next + offset) % self.slots.len();
+			if self.flush(index).is_ok() {
+				found = Some(index);
+				break;
+			}
+		}
+		let index = match found {
+			Some(index) => index,
+			None => {
+				// Every buffer is still being read, so start a new one that's already up to date
+				let slot = self.new_slot();
+				self.slots.push(slot);
+				self.slots.len() - 1
+			},
+		};
+		self.next = (index + 1) % self.slots.len();
+		self.bounds();
+		
+		let slot = &self.slots[index];
+		let verts = slot.verts.clone().into_buffer_slice().slice(0..self.geometry.verts.len()).unwrap();
+		Some(Frame {
+			verts: Arc::new(verts),
+			indices: slot.indices.as_ref().map(|buf| {
+				buf.clone().into_buffer_slice().slice(0..index_count.unwrap()).unwrap()
+			}),
+		})
+	}
+	
+	/// Copies the dirty ranges into a slot, failing if the GPU is still reading from it
+	fn flush(&mut self, index: usize) -> Result<(), ()> {
+		let slot = &mut self.slots[index];
+		let geometry = &self.geometry;
+		if let Some(range) = slot.dirty_verts.clone() {
+			let range = range.start..range.end.min(geometry.verts.len());
+			let mut lock = slot.verts.write().map_err(|_| ())?;
+			if range.start < range.end {
+				lock[range.clone()].copy_from_slice(&geometry.verts[range]);
+			}
+		}
+		if let (Some(range), Some(buf), Some(indices)) = (slot.dirty_indices.clone(), slot.indices.as_ref(), geometry.indices.as_ref()) {
+			let range = range.start..range.end.min(indices.len());
+			let mut lock = buf.write().map_err(|_| ())?;
+			if range.start < range.end {
+				lock[range.clone()].copy_from_slice(&indices[range]);
+			}
+		}
+		slot.dirty_verts = None;
+		slot.dirty_indices = None;
+		Ok(())
+	}
+}
+
+/// The smallest range covering both, for catching a buffer up on several changes at once
+pub(crate) fn union(range: Option<Range<usize>>, other: Range<usize>) -> Range<usize> {
+	match range {
+		Some(range) => range.start.min(other.start)..range.end.max(other.end),
+		None => other,
+	}
+}
+
+/// Checks that the indices make up whole primitives of vertices that exist, strips allowing the
+/// restart index between them
+pub(crate) fn validate(indices: &[u32], vertex_count: usize, topology: Topology) -> Result<(), ()> {
+	let restart = |i: u32| topology.is_strip() && i == ::std::u32::MAX;
+	if !topology.fits(indices.len()) || indices.iter().any(|&i| i as usize >= vertex_count && !restart(i)) {
+		return Err(());
+	}
+	Ok(())
+}
@@ -0,0 +1,385 @@
+//! Import of glTF 2.0 scenes (`.gltf` with embedded or external buffers, and `.glb`) and export of
+//! single meshes to `.glb`.
+//!
+//! The node hierarchy is flattened: every primitive of every mesh becomes an `Object` whose
+//! transform is the node's world transform.
+
+use std::io::{self, Write};
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use std::collections::HashMap;
+
+use cgmath::{Matrix4, SquareMatrix, PerspectiveFov, Ortho, Rad, Zero};
+
+use RenderInternal;
+use format::LoadError;
+use obj::{Mesh, MeshData, Object, Material, Topology, MorphTarget};
+use td::{Vertex, Color, Camera, Projection, Vec3};
+use animation::decompose;
+use geometry;
+use texture::Texture;
+
+pub struct Scene {
+	pub objects: Vec<(String, Object)>,
+	pub cameras: Vec<(String, Camera)>,
+}
+
+/// Imports the default scene of a glTF file, or the first one if no default is set
+pub fn load<P: AsRef<Path>>(internal: &RenderInternal, path: P) -> Result<Scene, LoadError> {
+	let (document, buffers, images) = ::gltf::import(path)?;
+	let contents = read(&document, &buffers)?;
+	
+	let mut textures = Textures {
+		images,
+		cache: HashMap::new(),
+	};
+	
+	let mut objects = Vec::new();
+	for primitive in contents.primitives {
+		let targets = primitive.targets;
+		let mesh = Mesh::new_with_topology(internal, primitive.data.verts, primitive.data.indices, primitive.topology)
+			.and_then(|mesh| mesh.with_morph_targets(internal, targets))
+			.map_err(|_| LoadError::InvalidMesh)?;
+		let mut object = Object::from_mesh(mesh);
+		object.transform = primitive.transform;
+		object.morph_weights = primitive.weights;
+		object.material = Material {
+			color: primitive.color,
+			texture: match primitive.texture {
+				Some(image) => Some(textures.get(internal, image, true)?),
+				None => None,
+			},
+			normal_map: match primitive.normal_map {
+				Some(image) => Some(textures.get(internal, image, false)?),
+				None => None,
+			},
+			..Material::default()
+		};
+		objects.push((primitive.name, object));
+	}
+	Ok(Scene {
+		objects,
+		cameras: contents.cameras,
+	})
+}
+
+/// A primitive as read from the file, before its mesh and textures are uploaded
+pub(crate) struct Primitive {
+	pub(crate) name: String,
+	pub(crate) data: MeshData,
+	pub(crate) topology: Topology,
+	pub(crate) targets: Vec<MorphTarget>,
+	pub(crate) transform: Matrix4<f32>,
+	pub(crate) weights: Vec<f32>,
+	pub(crate) color: Color,
+	/// The images of the base color texture and the normal map
+	pub(crate) texture: Option<usize>,
+	pub(crate) normal_map: Option<usize>,
+}
+
+/// Everything in a scene that doesn't need a device to read
+pub(crate) struct Contents {
+	pub(crate) primitives: Vec<Primitive>,
+	pub(crate) cameras: Vec<(String, Camera)>,
+}
+
+/// Reads the default scene of a document, or the first one if no default is set
+pub(crate) fn read(document: &::gltf::Document, buffers: &[::gltf::buffer::Data]) -> Result<Contents, LoadError> {
+	let scene = document.default_scene()
+		.or_else(|| document.scenes().next())
+		.ok_or_else(|| LoadError::Unsupported("file contains no scenes".to_string()))?;
+	
+	let mut out = Contents {
+		primitives: Vec::new(),
+		cameras: Vec::new(),
+	};
+	for node in scene.nodes() {
+		visit(node, Matrix4::identity(), buffers, &mut out)?;
+	}
+	Ok(out)
+}
+
+/// Writes mesh data to a binary glTF (`.glb`) file
+pub fn save_glb<P: AsRef<Path>>(path: P, data: &MeshData) -> io::Result<()> {
+	let mut file = File::create(path)?;
+	write_glb(&mut file, data)
+}
+
+/// Writes mesh data as a binary glTF containing a single node with a single mesh. Mesh data
+/// without indices is written as a triangle list in vertex order.
+pub fn write_glb<W: Write>(w: &mut W, data: &MeshData) -> io::Result<()> {
+	let count = data.verts.len();
+	let mut bin: Vec<u8> = Vec::with_capacity(count * 48 + data.indices.len() * 4);
+	let mut min = [::std::f32::INFINITY; 3];
+	let mut max = [::std::f32::NEG_INFINITY; 3];
+	
+	// Attributes are stored one after another, each in its own buffer view
+	for v in &data.verts {
+		for i in 0..3 {
+			min[i] = min[i].min(v.a_Pos[i]);
+			max[i] = max[i].max(v.a_Pos[i]);
+			bin.extend_from_slice(&v.a_Pos[i].to_le_bytes());
+		}
+	}
+	for v in &data.verts {
+		for f in &v.a_Normal {
+			bin.extend_from_slice(&f.to_le_bytes());
+		}
+	}
+	for v in &data.verts {
+		for f in &v.a_Color {
+			bin.extend_from_slice(&f.to_le_bytes());
+		}
+	}
+	for v in &data.verts {
+		for f in &v.a_TexCoord {
+			bin.extend_from_slice(&f.to_le_bytes());
+		}
+	}
+	for i in &data.indices {
+		bin.extend_from_slice(&i.to_le_bytes());
+	}
+	
+	if count == 0 {
+		min = [0.0; 3];
+		max = [0.0; 3];
+	}
+	let views = [(0, count * 12), (count * 12, count * 12), (count * 24, count * 16), (count * 40, count * 8)];
+	let mut buffer_views = views.iter()
+		.map(|&(offset, len)| format!("{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{},\"target\":34962}}", offset, len))
+		.collect::<Vec<_>>();
+	let mut accessors = vec![
+		format!("{{\"bufferView\":0,\"componentType\":5126,\"count\":{},\"type\":\"VEC3\",\"min\":[{},{},{}],\"max\":[{},{},{}]}}",
+		        count, min[0], min[1], min[2], max[0], max[1], max[2]),
+		format!("{{\"bufferView\":1,\"componentType\":5126,\"count\":{},\"type\":\"VEC3\"}}", count),
+		format!("{{\"bufferView\":2,\"componentType\":5126,\"count\":{},\"type\":\"VEC4\"}}", count),
+		format!("{{\"bufferView\":3,\"componentType\":5126,\"count\":{},\"type\":\"VEC2\"}}", count),
+	];
+	let primitive = if data.indices.is_empty() {
+		"\"mode\":4".to_string()
+	} else {
+		buffer_views.push(format!("{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{},\"target\":34963}}", count * 48, data.indices.len() * 4));
+		accessors.push(format!("{{\"bufferView\":4,\"componentType\":5125,\"count\":{},\"type\":\"SCALAR\"}}", data.indices.len()));
+		"\"indices\":4,\"mode\":4".to_string()
+	};
+	
+	let mut json = format!(concat!(
+		"{{\"asset\":{{\"version\":\"2.0\",\"generator\":\"vrender\"}},",
+		"\"scene\":0,\"scenes\":[{{\"nodes\":[0]}}],\"nodes\":[{{\"mesh\":0}}],",
+		"\"meshes\":[{{\"primitives\":[{{\"attributes\":{{\"POSITION\":0,\"NORMAL\":1,\"COLOR_0\":2,\"TEXCOORD_0\":3}},{}}}]}}],",
+		"\"accessors\":[{}],\"bufferViews\":[{}],\"buffers\":[{{\"byteLength\":{}}}]}}"),
+		primitive, accessors.join(","), buffer_views.join(","), bin.len()).into_bytes();
+	
+	// Both chunks must be 4-byte aligned, JSON is padded with spaces and binary data with zeros
+	while json.len() % 4 != 0 {
+		json.push(b' ');
+	}
+	while bin.len() % 4 != 0 {
+		bin.push(0);
+	}
+	
+	let total = 12 + 8 + json.len() + 8 + bin.len();
+	w.write_all(b"glTF")?;
+	w.write_all(&2u32.to_le_bytes())?;
+	w.write_all(&(total as u32).to_le_bytes())?;
+	w.write_all(&(json.len() as u32).to_le_bytes())?;
+	w.write_all(b"JSON")?;
+	w.write_all(&json)?;
+	w.write_all(&(bin.len() as u32).to_le_bytes())?;
+	w.write_all(b"BIN\0")?;
+	w.write_all(&bin)?;
+	Ok(())
+}
+
+fn visit(node: ::gltf::Node, parent: Matrix4<f32>, buffers: &[::gltf::buffer::Data], contents: &mut Contents) -> Result<(), LoadError> {
+	let transform = parent * Matrix4::from(node.transform().matrix());
+	let name = node.name()
+		.map(|n| n.to_string())
+		.unwrap_or_else(|| format!("node{}", node.index()));
+	
+	if let Some(mesh) = node.mesh() {
+		// The node can override the default morph target weights of the mesh
+		let weights = node.weights().or_else(|| mesh.weights()).map(|w| w.to_vec()).unwrap_or_default();
+		let primitives = mesh.primitives().collect::<Vec<_>>();
+		let count = primitives.len();
+		for (i, primitive) in primitives.into_iter().enumerate() {
+			let (mut data, topology) = read_primitive(&primitive, buffers)?;
+			let mut targets = read_morph_targets(&primitive, buffers, data.verts.len());
+			// Normal maps need tangents, which are expected to be generated when the file has none
+			let material = primitive.material();
+			let needs_tangents = material.normal_texture().is_some()
+				&& primitive.get(&::gltf::Semantic::Tangents).is_none();
+			if needs_tangents && topology == Topology::TriangleList {
+				let sources = geometry::generate_tangents_mapped(&mut data);
+				for target in &mut targets {
+					target.remap(&sources);
+				}
+			}
+			let pbr = material.pbr_metallic_roughness();
+			let c = pbr.base_color_factor();
+			
+			contents.primitives.push(Primitive {
+				name: if count > 1 { format!("{}#{}", name, i) } else { name.clone() },
+				data,
+				topology,
+				targets,
+				transform,
+				weights: weights.clone(),
+				color: Color::new(c[0], c[1], c[2], c[3]),
+				texture: pbr.base_color_texture().map(|info| info.texture().source().index()),
+				normal_map: material.normal_texture().map(|normal| normal.texture().source().index()),
+			});
+		}
+	}
+	
+	if let Some(camera) = node.camera() {
+		contents.cameras.push((name.clone(), convert_camera(camera, transform)));
+	}
+	
+	for child in node.children() {
+		visit(child, transform, buffers, contents)?;
+	}
+	Ok(())
+}
+
+fn read_primitive(primitive: &::gltf::Primitive, buffers: &[::gltf::buffer::Data]) -> Result<(MeshData, Topology), LoadError> {
+	use gltf::mesh::Mode;
+	
+	let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+	let positions = reader.read_positions()
+		.ok_or_else(|| LoadError::Unsupported("primitive without positions".to_string()))?;
+	
+	let mut verts = positions.map(|p| Vertex {
+		a_Pos: [p[0], p[1], p[2], 1.0],
+		a_Color: Color::white().raw(),
+		a_Normal: [0.0, 0.0, 0.0],
+		a_TexCoord: [0.0, 0.0],
+		a_Tangent: [0.0, 0.0, 0.0, 0.0],
+	}).collect::<Vec<_>>();
+	
+	if let Some(normals) = reader.read_normals() {
+		for (v, n) in verts.iter_mut().zip(normals) {
+			v.a_Normal = n;
+		}
+	}
+	if let Some(colors) = reader.read_colors(0) {
+		for (v, c) in verts.iter_mut().zip(colors.into_rgba_f32()) {
+			v.a_Color = c;
+		}
+	}
+	if let Some(texcoords) = reader.read_tex_coords(0) {
+		for (v, t) in verts.iter_mut().zip(texcoords.into_f32()) {
+			v.a_TexCoord = t;
+		}
+	}
+	if let Some(tangents) = reader.read_tangents() {
+		for (v, t) in verts.iter_mut().zip(tangents) {
+			v.a_Tangent = t;
+		}
+	}
+	
+	// Non-indexed primitives are drawn in vertex order
+	let mut indices: Vec<u32> = match reader.read_indices() {
+		Some(indices) => indices.into_u32().collect(),
+		None => (0..verts.len() as u32).collect(),
+	};
+	
+	// Loops and fans have no Vulkan equivalent, so they're turned into a strip and a list
+	let topology = match primitive.mode() {
+		Mode::Points => Topology::PointList,
+		Mode::Lines => Topology::LineList,
+		Mode::LineStrip => Topology::LineStrip,
+		Mode::LineLoop => {
+			if let Some(&first) = indices.first() {
+				indices.push(first);
+			}
+			Topology::LineStrip
+		},
+		Mode::Triangles => Topology::TriangleList,
+		Mode::TriangleStrip => Topology::TriangleStrip,
+		Mode::TriangleFan => {
+			indices = (2..indices.len())
+				.flat_map(|k| vec![indices[0], indices[k - 1], indices[k]])
+				.collect();
+			Topology::TriangleList
+		},
+	};
+	
+	Ok((MeshData::new(verts, indices), topology))
+}
+
+fn read_morph_targets(primitive: &::gltf::Primitive, buffers: &[::gltf::buffer::Data], count: usize) -> Vec<MorphTarget> {
+	let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+	reader.read_morph_targets().enumerate().map(|(i, (positions, normals, _))| MorphTarget {
+		name: format!("target{}", i),
+		positions: match positions {
+			Some(positions) => positions.map(Vec3::from).collect(),
+			None => vec![Vec3::zero(); count],
+		},
+		normals: normals.map(|normals| normals.map(Vec3::from).collect()),
+	}).collect()
+}
+
+/// Images are only uploaded once they're used, since the same image must be decoded differently
+/// depending on whether it holds colors or normals
+struct Textures {
+	images: Vec<::gltf::image::Data>,
+	cache: HashMap<(usize, bool), Arc<Texture>>,
+}
+
+impl Textures {
+	fn get(&mut self, internal: &RenderInternal, index: usize, srgb: bool) -> Result<Arc<Texture>, LoadError> {
+		if let Some(texture) = self.cache.get(&(index, srgb)) {
+			return Ok(Arc::clone(texture));
+		}
+		let texture = Arc::new(convert_image(internal, &self.images[index], srgb)?);
+		self.cache.insert((index, srgb), Arc::clone(&texture));
+		Ok(texture)
+	}
+}
+
+fn convert_image(internal: &RenderInternal, image: &::gltf::image::Data, srgb: bool) -> Result<Texture, LoadError> {
+	use gltf::image::Format;
+	
+	let pixels = match image.format {
+		Format::R8G8B8A8 => image.pixels.clone(),
+		Format::R8G8B8 => image.pixels.chunks(3).flat_map(|p| vec![p[0], p[1], p[2], 255]).collect(),
+		Format::R8G8 => image.pixels.chunks(2).flat_map(|p| vec![p[0], p[0], p[0], p[1]]).collect(),
+		Format::R8 => image.pixels.iter().flat_map(|&p| vec![p, p, p, 255]).collect(),
+	};
+	
+	let texture = if srgb {
+		Texture::from_rgba(internal, image.width, image.height, pixels)
+	} else {
+		Texture::from_rgba_linear(internal, image.width, image.height, pixels)
+	};
+	texture.map_err(|_| LoadError::Unsupported("image dimensions".to_string()))
+}
+
+fn convert_camera(camera: ::gltf::Camera, transform: Matrix4<f32>) -> Camera {
+	let proj = match camera.projection() {
+		::gltf::camera::Projection::Perspective(p) => Projection::Perspective(PerspectiveFov {
+			fovy: Rad(p.yfov()),
+			aspect: p.aspect_ratio().unwrap_or(1.0),
+			near: p.znear(),
+			far: p.zfar().unwrap_or(1000.0),
+		}),
+		// The magnifications are half the width and height of the view
+		::gltf::camera::Projection::Orthographic(o) => Projection::OrthographicBounds(Ortho {
+			left: -o.xmag(),
+			right: o.xmag(),
+			bottom: -o.ymag(),
+			top: o.ymag(),
+			near: o.znear(),
+			far: o.zfar(),
+		}),
+	};
+	
+	// glTF cameras look down their local -Z axis with Y up, the same as a camera's orientation
+	let trs = decompose(&transform);
+	let mut out = Camera::new(proj);
+	out.set_pos(trs.translation);
+	out.set_orientation(trs.rotation);
+	out
+}
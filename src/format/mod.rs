@@ -0,0 +1,86 @@
+use std::io;
+use std::fmt;
+use std::error::Error;
+
+pub mod wavefront;
+pub mod gltf;
+pub mod stl;
+pub mod ply;
+
+use obj::MeshData;
+use geometry::{self, NormalMode, Weighting};
+
+/// Post-processing applied by the loaders that support it
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LoadOptions {
+	/// Merge identical vertices into a shared index buffer
+	pub weld: bool,
+	/// Replace any normals in the file with generated smooth ones
+	pub generate_normals: bool,
+	/// Reorder the result for the GPU's caches, see `geometry::optimize`
+	pub optimize: bool,
+}
+
+impl LoadOptions {
+	pub(crate) fn apply(&self, data: &mut MeshData) {
+		if self.weld {
+			geometry::weld(data);
+		}
+		if self.generate_normals {
+			geometry::generate_normals(data, NormalMode::Smooth { weighting: Weighting::Area, crease: None });
+		}
+		if self.optimize {
+			geometry::optimize(data);
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+	Io(io::Error),
+	Gltf(::gltf::Error),
+	/// A syntax or semantic error on a specific (1-based) line of the source file
+	Parse { line: usize, msg: String },
+	/// Binary data that ends early or contradicts its own header
+	Corrupt(String),
+	/// The data was read fine but couldn't be turned into a valid `Mesh`
+	InvalidMesh,
+	/// The file is valid but uses a feature we can't import
+	Unsupported(String),
+}
+
+impl LoadError {
+	pub(crate) fn parse<S: Into<String>>(line: usize, msg: S) -> Self {
+		LoadError::Parse {
+			line,
+			msg: msg.into(),
+		}
+	}
+}
+
+impl From<io::Error> for LoadError {
+	fn from(e: io::Error) -> Self {
+		LoadError::Io(e)
+	}
+}
+
+impl From<::gltf::Error> for LoadError {
+	fn from(e: ::gltf::Error) -> Self {
+		LoadError::Gltf(e)
+	}
+}
+
+impl fmt::Display for LoadError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			LoadError::Io(ref e) => write!(f, "I/O error: {}", e),
+			LoadError::Gltf(ref e) => write!(f, "glTF error: {}", e),
+			LoadError::Parse { line, ref msg } => write!(f, "line {}: {}", line, msg),
+			LoadError::Corrupt(ref msg) => write!(f, "corrupt data: {}", msg),
+			LoadError::InvalidMesh => write!(f, "invalid mesh data"),
+			LoadError::Unsupported(ref what) => write!(f, "unsupported: {}", what),
+		}
+	}
+}
+
+impl Error for LoadError {}
@@ -0,0 +1,445 @@
+//! Loader for ASCII and binary PLY files.
+//!
+//! Vertex positions, normals, colors and texture coordinates are read from the `vertex` element
+//! and polygons from the `face` element. Files without faces (point clouds) produce `MeshData`
+//! with an empty index buffer. Any other elements and properties are skipped.
+
+use std::io::{self, Read, Write, BufWriter};
+use std::fs::File;
+use std::path::Path;
+use std::str::{self, SplitWhitespace, Lines};
+use std::iter::Enumerate;
+
+use RenderInternal;
+use format::{LoadError, LoadOptions};
+use obj::{Mesh, MeshData, Topology};
+use td::{Vertex, Color};
+
+pub fn load<P: AsRef<Path>>(path: P, options: &LoadOptions) -> Result<MeshData, LoadError> {
+	let mut bytes = Vec::new();
+	File::open(path)?.read_to_end(&mut bytes)?;
+	parse(&bytes, options)
+}
+
+/// Loads a PLY file and uploads it to the GPU. Files without faces become a point list.
+pub fn load_mesh<P: AsRef<Path>>(internal: &RenderInternal, path: P, options: &LoadOptions) -> Result<Mesh, LoadError> {
+	let data = load(path, options)?;
+	if data.indices.is_empty() {
+		Mesh::new_pure_with_topology(internal, data.verts, Topology::PointList)
+	} else {
+		Mesh::from_data(internal, data)
+	}.map_err(|_| LoadError::InvalidMesh)
+}
+
+/// Writes mesh data to a binary little endian PLY file
+pub fn save<P: AsRef<Path>>(path: P, data: &MeshData) -> io::Result<()> {
+	let mut w = BufWriter::new(File::create(path)?);
+	write(&mut w, data)?;
+	w.flush()
+}
+
+/// Writes mesh data as binary little endian PLY. Colors are quantized to 8 bits per channel. Mesh
+/// data without indices is written as a triangle list in vertex order.
+pub fn write<W: Write>(w: &mut W, data: &MeshData) -> io::Result<()> {
+	let indices = data.triangle_indices();
+	let faces = indices.len() / 3;
+	writeln!(w, "ply\nformat binary_little_endian 1.0\ncomment written by vrender")?;
+	writeln!(w, "element vertex {}", data.verts.len())?;
+	for name in &["x", "y", "z", "nx", "ny", "nz"] {
+		writeln!(w, "property float {}", name)?;
+	}
+	for name in &["red", "green", "blue", "alpha"] {
+		writeln!(w, "property uchar {}", name)?;
+	}
+	writeln!(w, "property float s\nproperty float t")?;
+	if faces > 0 {
+		writeln!(w, "element face {}\nproperty list uchar uint vertex_indices", faces)?;
+	}
+	writeln!(w, "end_header")?;
+	
+	for v in &data.verts {
+		for f in v.a_Pos[..3].iter().chain(v.a_Normal.iter()) {
+			w.write_all(&f.to_le_bytes())?;
+		}
+		for c in &v.a_Color {
+			w.write_all(&[(c.max(0.0).min(1.0) * 255.0).round() as u8])?;
+		}
+		for f in &v.a_TexCoord {
+			w.write_all(&f.to_le_bytes())?;
+		}
+	}
+	for tri in indices.chunks(3).take(faces) {
+		w.write_all(&[3])?;
+		for i in tri {
+			w.write_all(&i.to_le_bytes())?;
+		}
+	}
+	Ok(())
+}
+
+pub fn parse(bytes: &[u8], options: &LoadOptions) -> Result<MeshData, LoadError> {
+	let (header, body, body_line) = split_header(bytes)?;
+	let (encoding, elements) = parse_header(header)?;
+	
+	let mut data = MeshData::default();
+	match encoding {
+		Encoding::Ascii => {
+			let text = str::from_utf8(body).map_err(|_| LoadError::Corrupt("ASCII PLY is not valid UTF-8".to_string()))?;
+			let mut reader = AsciiReader {
+				lines: text.lines().enumerate(),
+				first_line: body_line,
+				line: body_line,
+				tokens: "".split_whitespace(),
+			};
+			read_elements(&mut reader, &elements, body.len(), &mut data)?;
+		},
+		Encoding::LittleEndian | Encoding::BigEndian => {
+			let mut reader = BinaryReader {
+				bytes: body,
+				pos: 0,
+				big_endian: encoding == Encoding::BigEndian,
+			};
+			read_elements(&mut reader, &elements, body.len(), &mut data)?;
+		},
+	}
+	
+	options.apply(&mut data);
+	Ok(data)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Encoding {
+	Ascii,
+	LittleEndian,
+	BigEndian,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Scalar {
+	I8,
+	U8,
+	I16,
+	U16,
+	I32,
+	U32,
+	F32,
+	F64,
+}
+
+impl Scalar {
+	fn parse(name: &str) -> Option<Scalar> {
+		Some(match name {
+			"char" | "int8" => Scalar::I8,
+			"uchar" | "uint8" => Scalar::U8,
+			"short" | "int16" => Scalar::I16,
+			"ushort" | "uint16" => Scalar::U16,
+			"int" | "int32" => Scalar::I32,
+			"uint" | "uint32" => Scalar::U32,
+			"float" | "float32" => Scalar::F32,
+			"double" | "float64" => Scalar::F64,
+			_ => return None,
+		})
+	}
+	
+	fn size(self) -> usize {
+		match self {
+			Scalar::I8 | Scalar::U8 => 1,
+			Scalar::I16 | Scalar::U16 => 2,
+			Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+			Scalar::F64 => 8,
+		}
+	}
+	
+	/// The factor that maps integer color channels to 0..1
+	fn color_scale(self) -> f32 {
+		match self {
+			Scalar::U8 => 1.0 / 255.0,
+			Scalar::U16 => 1.0 / 65535.0,
+			_ => 1.0,
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+enum Property {
+	Scalar(String, Scalar),
+	List(String, Scalar, Scalar),
+}
+
+#[derive(Clone, Debug)]
+struct Element {
+	name: String,
+	count: usize,
+	props: Vec<Property>,
+}
+
+/// Returns the header text, the body bytes and the line number the body starts on
+fn split_header(bytes: &[u8]) -> Result<(&str, &[u8], usize), LoadError> {
+	if !bytes.starts_with(b"ply") {
+		return Err(LoadError::Corrupt("not a PLY file".to_string()));
+	}
+	let marker = b"end_header";
+	let end = bytes.windows(marker.len())
+		.position(|w| w == marker)
+		.ok_or_else(|| LoadError::Corrupt("missing 'end_header'".to_string()))?;
+	let mut body = end + marker.len();
+	// The header ends with a single line break, which may be CRLF
+	if bytes.get(body) == Some(&b'\r') {
+		body += 1;
+	}
+	if bytes.get(body) == Some(&b'\n') {
+		body += 1;
+	}
+	let header = str::from_utf8(&bytes[..end]).map_err(|_| LoadError::Corrupt("PLY header is not valid UTF-8".to_string()))?;
+	Ok((header, &bytes[body..], header.lines().count() + 2))
+}
+
+fn parse_header(header: &str) -> Result<(Encoding, Vec<Element>), LoadError> {
+	let mut encoding = None;
+	let mut elements: Vec<Element> = Vec::new();
+	
+	for (i, line) in header.lines().enumerate().skip(1) {
+		let num = i + 1;
+		let mut parts = line.split_whitespace();
+		match parts.next() {
+			Some("format") => {
+				encoding = Some(match parts.next() {
+					Some("ascii") => Encoding::Ascii,
+					Some("binary_little_endian") => Encoding::LittleEndian,
+					Some("binary_big_endian") => Encoding::BigEndian,
+					_ => return Err(LoadError::parse(num, "unknown format")),
+				});
+			},
+			Some("element") => {
+				let name = parts.next().ok_or_else(|| LoadError::parse(num, "missing element name"))?;
+				let count = parts.next()
+					.and_then(|c| c.parse().ok())
+					.ok_or_else(|| LoadError::parse(num, "missing or invalid element count"))?;
+				elements.push(Element {
+					name: name.to_string(),
+					count,
+					props: Vec::new(),
+				});
+			},
+			Some("property") => {
+				let element = elements.last_mut().ok_or_else(|| LoadError::parse(num, "property before any element"))?;
+				let ty = parts.next().ok_or_else(|| LoadError::parse(num, "missing property type"))?;
+				let scalar = |name: Option<&str>| {
+					name.and_then(Scalar::parse).ok_or_else(|| LoadError::parse(num, "unknown property type"))
+				};
+				let prop = if ty == "list" {
+					let count = scalar(parts.next())?;
+					let item = scalar(parts.next())?;
+					let name = parts.next().ok_or_else(|| LoadError::parse(num, "missing property name"))?;
+					Property::List(name.to_string(), count, item)
+				} else {
+					let ty = scalar(Some(ty))?;
+					let name = parts.next().ok_or_else(|| LoadError::parse(num, "missing property name"))?;
+					Property::Scalar(name.to_string(), ty)
+				};
+				element.props.push(prop);
+			},
+			_ => {},
+		}
+	}
+	
+	let encoding = encoding.ok_or_else(|| LoadError::Corrupt("missing 'format' line".to_string()))?;
+	Ok((encoding, elements))
+}
+
+trait ValueReader {
+	/// Called before each element instance
+	fn begin(&mut self) -> Result<(), LoadError>;
+	fn read(&mut self, ty: Scalar) -> Result<f64, LoadError>;
+}
+
+struct AsciiReader<'a> {
+	lines: Enumerate<Lines<'a>>,
+	first_line: usize,
+	line: usize,
+	tokens: SplitWhitespace<'a>,
+}
+
+impl<'a> ValueReader for AsciiReader<'a> {
+	fn begin(&mut self) -> Result<(), LoadError> {
+		loop {
+			match self.lines.next() {
+				Some((i, line)) => {
+					self.line = self.first_line + i;
+					if !line.trim().is_empty() {
+						self.tokens = line.split_whitespace();
+						return Ok(())
+					}
+				},
+				None => return Err(LoadError::parse(self.line, "unexpected end of file")),
+			}
+		}
+	}
+	
+	fn read(&mut self, _ty: Scalar) -> Result<f64, LoadError> {
+		let line = self.line;
+		let token = self.tokens.next().ok_or_else(|| LoadError::parse(line, "too few values"))?;
+		token.parse().map_err(|_| LoadError::parse(line, format!("invalid number '{}'", token)))
+	}
+}
+
+struct BinaryReader<'a> {
+	bytes: &'a [u8],
+	pos: usize,
+	big_endian: bool,
+}
+
+impl<'a> ValueReader for BinaryReader<'a> {
+	fn begin(&mut self) -> Result<(), LoadError> {
+		Ok(())
+	}
+	
+	fn read(&mut self, ty: Scalar) -> Result<f64, LoadError> {
+		let size = ty.size();
+		if self.pos + size > self.bytes.len() {
+			return Err(LoadError::Corrupt("unexpected end of data".to_string()));
+		}
+		let mut b = [0u8; 8];
+		b[..size].copy_from_slice(&self.bytes[self.pos..self.pos + size]);
+		if self.big_endian {
+			b[..size].reverse();
+		}
+		self.pos += size;
+		
+		Ok(match ty {
+			Scalar::I8 => b[0] as i8 as f64,
+			Scalar::U8 => b[0] as f64,
+			Scalar::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+			Scalar::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+			Scalar::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+			Scalar::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+			Scalar::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+			Scalar::F64 => f64::from_le_bytes(b),
+		})
+	}
+}
+
+/// What a vertex property is stored into
+#[derive(Copy, Clone)]
+enum Target {
+	Pos(usize),
+	Normal(usize),
+	Color(usize, f32),
+	TexCoord(usize),
+	Ignore,
+}
+
+/// Counts in the header aren't trusted for allocating, every value takes at least one of the
+/// `body_len` bytes so no element can have more instances than that
+fn read_elements<R: ValueReader>(reader: &mut R, elements: &[Element], body_len: usize, data: &mut MeshData) -> Result<(), LoadError> {
+	for element in elements {
+		match &element.name[..] {
+			"vertex" => read_vertices(reader, element, body_len, data)?,
+			"face" => read_faces(reader, element, body_len, data)?,
+			_ => {
+				for _ in 0..element.count {
+					reader.begin()?;
+					for prop in &element.props {
+						skip(reader, prop)?;
+					}
+				}
+			},
+		}
+	}
+	
+	let count = data.verts.len() as u32;
+	if data.indices.iter().any(|&i| i >= count) {
+		return Err(LoadError::Corrupt("face refers to a vertex that doesn't exist".to_string()));
+	}
+	Ok(())
+}
+
+fn read_vertices<R: ValueReader>(reader: &mut R, element: &Element, body_len: usize, data: &mut MeshData) -> Result<(), LoadError> {
+	let targets = element.props.iter().map(|prop| match *prop {
+		Property::Scalar(ref name, ty) => match &name[..] {
+			"x" => Target::Pos(0),
+			"y" => Target::Pos(1),
+			"z" => Target::Pos(2),
+			"nx" => Target::Normal(0),
+			"ny" => Target::Normal(1),
+			"nz" => Target::Normal(2),
+			"red" | "r" => Target::Color(0, ty.color_scale()),
+			"green" | "g" => Target::Color(1, ty.color_scale()),
+			"blue" | "b" => Target::Color(2, ty.color_scale()),
+			"alpha" | "a" => Target::Color(3, ty.color_scale()),
+			"u" | "s" | "texture_u" => Target::TexCoord(0),
+			"v" | "t" | "texture_v" => Target::TexCoord(1),
+			_ => Target::Ignore,
+		},
+		Property::List(..) => Target::Ignore,
+	}).collect::<Vec<_>>();
+	
+	data.verts.reserve(element.count.min(body_len));
+	for _ in 0..element.count {
+		reader.begin()?;
+		let mut v = Vertex::new(0.0, 0.0, 0.0, 1.0, Color::white());
+		for (prop, target) in element.props.iter().zip(&targets) {
+			let ty = match *prop {
+				Property::Scalar(_, ty) => ty,
+				Property::List(..) => {
+					skip(reader, prop)?;
+					continue;
+				},
+			};
+			let value = reader.read(ty)? as f32;
+			match *target {
+				Target::Pos(i) => v.a_Pos[i] = value,
+				Target::Normal(i) => v.a_Normal[i] = value,
+				Target::Color(i, scale) => v.a_Color[i] = value * scale,
+				Target::TexCoord(i) => v.a_TexCoord[i] = value,
+				Target::Ignore => {},
+			}
+		}
+		data.verts.push(v);
+	}
+	Ok(())
+}
+
+fn read_faces<R: ValueReader>(reader: &mut R, element: &Element, body_len: usize, data: &mut MeshData) -> Result<(), LoadError> {
+	let mut face = Vec::new();
+	data.indices.reserve(element.count.checked_mul(3).unwrap_or(body_len).min(body_len));
+	for _ in 0..element.count {
+		reader.begin()?;
+		for prop in &element.props {
+			match *prop {
+				Property::List(ref name, count_ty, item_ty) if name == "vertex_indices" || name == "vertex_index" => {
+					let count = reader.read(count_ty)? as usize;
+					face.clear();
+					for _ in 0..count {
+						let index = reader.read(item_ty)?;
+						if index < 0.0 {
+							return Err(LoadError::Corrupt(format!("negative vertex index {}", index)));
+						}
+						face.push(index as u32);
+					}
+					// Triangulate as a fan, polygons with fewer than three corners produce nothing
+					for j in 1..count.saturating_sub(1) {
+						data.indices.extend_from_slice(&[face[0], face[j], face[j + 1]]);
+					}
+				},
+				_ => skip(reader, prop)?,
+			}
+		}
+	}
+	Ok(())
+}
+
+fn skip<R: ValueReader>(reader: &mut R, prop: &Property) -> Result<(), LoadError> {
+	match *prop {
+		Property::Scalar(_, ty) => {
+			reader.read(ty)?;
+		},
+		Property::List(_, count_ty, item_ty) => {
+			let count = reader.read(count_ty)? as usize;
+			for _ in 0..count {
+				reader.read(item_ty)?;
+			}
+		},
+	}
+	Ok(())
+}
@@ -0,0 +1,153 @@
+//! Loader for binary and ASCII STL files.
+//!
+//! STL stores every triangle separately with a facet normal, so without welding each triangle
+//! gets three vertices carrying its facet normal. Welding drops the facet normals so that shared
+//! corners can merge, and smooth normals are generated afterwards.
+
+use std::io::Read;
+use std::fs::File;
+use std::path::Path;
+use std::str;
+
+use cgmath::{Vector3, InnerSpace};
+
+use RenderInternal;
+use format::{LoadError, LoadOptions};
+use obj::{Mesh, MeshData};
+use td::{Vertex, Color};
+
+/// Loads an STL file, detecting whether it is binary or ASCII
+pub fn load<P: AsRef<Path>>(path: P, options: &LoadOptions) -> Result<MeshData, LoadError> {
+	let mut bytes = Vec::new();
+	File::open(path)?.read_to_end(&mut bytes)?;
+	parse(&bytes, options)
+}
+
+/// Loads an STL file and uploads it to the GPU
+pub fn load_mesh<P: AsRef<Path>>(internal: &RenderInternal, path: P, options: &LoadOptions) -> Result<Mesh, LoadError> {
+	Mesh::from_data(internal, load(path, options)?).map_err(|_| LoadError::InvalidMesh)
+}
+
+pub fn parse(bytes: &[u8], options: &LoadOptions) -> Result<MeshData, LoadError> {
+	let mut data = if is_binary(bytes) {
+		parse_binary(bytes)?
+	} else if bytes.starts_with(b"solid") {
+		parse_ascii(bytes)?
+	} else {
+		return Err(LoadError::Corrupt("not an STL file".to_string()))
+	};
+	
+	if options.weld {
+		for v in &mut data.verts {
+			v.a_Normal = [0.0, 0.0, 0.0];
+		}
+		LoadOptions { weld: true, generate_normals: true, ..*options }.apply(&mut data);
+	} else {
+		options.apply(&mut data);
+	}
+	Ok(data)
+}
+
+/// Binary files may also start with "solid", so the size implied by the header decides. Some
+/// exporters pad binary files, so they're allowed to be longer than that.
+fn is_binary(bytes: &[u8]) -> bool {
+	if bytes.len() < 84 {
+		return false;
+	}
+	let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+	bytes.len() >= 84 + count * 50
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<MeshData, LoadError> {
+	// Anything after the facets the header counts is padding
+	let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+	let mut data = MeshData {
+		verts: Vec::with_capacity(count * 3),
+		indices: Vec::with_capacity(count * 3),
+	};
+	
+	for facet in bytes[84..84 + count * 50].chunks(50) {
+		let f = |i: usize| {
+			let o = i * 4;
+			f32::from_le_bytes([facet[o], facet[o + 1], facet[o + 2], facet[o + 3]])
+		};
+		let normal = Vector3::new(f(0), f(1), f(2));
+		let corners = [
+			Vector3::new(f(3), f(4), f(5)),
+			Vector3::new(f(6), f(7), f(8)),
+			Vector3::new(f(9), f(10), f(11)),
+		];
+		push_facet(&mut data, normal, corners);
+	}
+	Ok(data)
+}
+
+fn parse_ascii(bytes: &[u8]) -> Result<MeshData, LoadError> {
+	let text = str::from_utf8(bytes).map_err(|_| LoadError::Corrupt("ASCII STL is not valid UTF-8".to_string()))?;
+	let mut data = MeshData::default();
+	let mut normal = Vector3::new(0.0, 0.0, 0.0);
+	let mut corners = Vec::with_capacity(3);
+	
+	for (i, line) in text.lines().enumerate() {
+		let num = i + 1;
+		let mut parts = line.split_whitespace();
+		match parts.next() {
+			Some("facet") => {
+				if parts.next() != Some("normal") {
+					return Err(LoadError::parse(num, "expected 'facet normal'"));
+				}
+				let v = floats3(parts, num)?;
+				normal = Vector3::new(v[0], v[1], v[2]);
+				corners.clear();
+			},
+			Some("vertex") => {
+				if corners.len() == 3 {
+					return Err(LoadError::parse(num, "facet has more than three vertices"));
+				}
+				let v = floats3(parts, num)?;
+				corners.push(Vector3::new(v[0], v[1], v[2]));
+			},
+			Some("endfacet") => {
+				if corners.len() != 3 {
+					return Err(LoadError::parse(num, "facet has fewer than three vertices"));
+				}
+				push_facet(&mut data, normal, [corners[0], corners[1], corners[2]]);
+			},
+			_ => {},
+		}
+	}
+	Ok(data)
+}
+
+fn floats3<'a, I: Iterator<Item = &'a str>>(parts: I, line: usize) -> Result<[f32; 3], LoadError> {
+	let mut out = [0.0; 3];
+	let mut count = 0;
+	for p in parts {
+		if count == 3 {
+			return Err(LoadError::parse(line, "expected three values"));
+		}
+		out[count] = p.parse().map_err(|_| LoadError::parse(line, format!("invalid number '{}'", p)))?;
+		count += 1;
+	}
+	if count != 3 {
+		return Err(LoadError::parse(line, "expected three values"));
+	}
+	Ok(out)
+}
+
+fn push_facet(data: &mut MeshData, normal: Vector3<f32>, corners: [Vector3<f32>; 3]) {
+	// Many exporters leave the facet normal zeroed
+	let normal = if normal.magnitude2() > 0.0 {
+		normal.normalize()
+	} else {
+		let n = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+		if n.magnitude2() > 0.0 { n.normalize() } else { n }
+	};
+	
+	for c in &corners {
+		let mut v = Vertex::new(c.x, c.y, c.z, 1.0, Color::white());
+		v.a_Normal = *normal.as_ref();
+		data.indices.push(data.verts.len() as u32);
+		data.verts.push(v);
+	}
+}
@@ -0,0 +1,340 @@
+//! Loader for Wavefront `.obj` files and their `.mtl` material libraries.
+//!
+//! Polygons are triangulated as fans, and every distinct position/texcoord/normal combination
+//! becomes a single vertex in the index buffer. A new mesh is started whenever the object, group
+//! or material changes. Vertex colors written after the position (`v x y z r g b`, with an
+//! optional alpha) are read, and vertices without one take the diffuse color of their material.
+
+use std::io::{self, BufRead, BufReader, Write, BufWriter};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::str::SplitWhitespace;
+
+use RenderInternal;
+use format::LoadError;
+use obj::{Mesh, MeshData};
+use td::{Vertex, Color};
+
+#[derive(Clone, Debug)]
+pub struct Material {
+	pub name: String,
+	pub diffuse: Color,
+	pub diffuse_map: Option<PathBuf>,
+}
+
+impl Material {
+	fn new(name: String) -> Self {
+		Material {
+			name,
+			diffuse: Color::white(),
+			diffuse_map: None,
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct ObjMesh {
+	pub name: String,
+	pub material: Option<Material>,
+	pub data: MeshData,
+}
+
+/// Loads every mesh in an `.obj` file. Material libraries are resolved relative to the file.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<ObjMesh>, LoadError> {
+	let path = path.as_ref();
+	let file = File::open(path)?;
+	parse(BufReader::new(file), path.parent())
+}
+
+/// Loads an `.obj` file and uploads each of its meshes to the GPU
+pub fn load_meshes<P: AsRef<Path>>(internal: &RenderInternal, path: P) -> Result<Vec<(String, Mesh)>, LoadError> {
+	load(path)?.into_iter().map(|m| {
+		let name = m.name;
+		Mesh::from_data(internal, m.data)
+			.map(|mesh| (name, mesh))
+			.map_err(|_| LoadError::InvalidMesh)
+	}).collect()
+}
+
+/// Writes mesh data to an `.obj` file
+pub fn save<P: AsRef<Path>>(path: P, data: &MeshData) -> io::Result<()> {
+	let mut w = BufWriter::new(File::create(path)?);
+	write(&mut w, data)?;
+	w.flush()
+}
+
+/// Writes mesh data as `.obj` text. Vertex colors are written after the positions
+/// (`v x y z r g b a`), which most tools understand and the rest ignore. Mesh data without indices
+/// is written as a triangle list in vertex order.
+pub fn write<W: Write>(w: &mut W, data: &MeshData) -> io::Result<()> {
+	for v in &data.verts {
+		let (p, c) = (v.a_Pos, v.a_Color);
+		writeln!(w, "v {} {} {} {} {} {} {}", p[0], p[1], p[2], c[0], c[1], c[2], c[3])?;
+	}
+	for v in &data.verts {
+		writeln!(w, "vt {} {}", v.a_TexCoord[0], 1.0 - v.a_TexCoord[1])?;
+	}
+	for v in &data.verts {
+		let n = v.a_Normal;
+		writeln!(w, "vn {} {} {}", n[0], n[1], n[2])?;
+	}
+	for tri in data.triangle_indices().chunks(3) {
+		if tri.len() < 3 {
+			break;
+		}
+		let (a, b, c) = (tri[0] + 1, tri[1] + 1, tri[2] + 1);
+		writeln!(w, "f {}/{}/{} {}/{}/{} {}/{}/{}", a, a, a, b, b, b, c, c, c)?;
+	}
+	Ok(())
+}
+
+/// Parses `.obj` data. If `base` is `None`, `mtllib` statements are ignored.
+pub fn parse<R: BufRead>(reader: R, base: Option<&Path>) -> Result<Vec<ObjMesh>, LoadError> {
+	let mut positions: Vec<[f32; 4]> = Vec::new();
+	let mut colors: Vec<Option<[f32; 4]>> = Vec::new();
+	let mut texcoords: Vec<[f32; 2]> = Vec::new();
+	let mut normals: Vec<[f32; 3]> = Vec::new();
+	let mut materials: HashMap<String, Material> = HashMap::new();
+	
+	let mut meshes = Vec::new();
+	let mut builder = Builder::new(String::new(), None);
+	
+	for (i, line) in reader.lines().enumerate() {
+		let line = line?;
+		let num = i + 1;
+		let line = match line.find('#') {
+			Some(idx) => &line[..idx],
+			None => &line[..],
+		};
+		let mut parts = line.split_whitespace();
+		let keyword = match parts.next() {
+			Some(k) => k,
+			None => continue,
+		};
+		
+		match keyword {
+			"v" => {
+				// Either a position with an optional w, or one followed by an RGB or RGBA color
+				let v = floats(parts, num, 3, 7)?;
+				let (w, color) = match v.len() {
+					3 => (1.0, None),
+					4 => (v[3], None),
+					6 => (1.0, Some([v[3], v[4], v[5], 1.0])),
+					7 => (1.0, Some([v[3], v[4], v[5], v[6]])),
+					n => return Err(LoadError::parse(num, format!("expected 3, 4, 6 or 7 values, found {}", n))),
+				};
+				positions.push([v[0], v[1], v[2], w]);
+				colors.push(color);
+			},
+			"vt" => {
+				let v = floats(parts, num, 1, 3)?;
+				// OBJ texture space has its origin at the bottom left, Vulkan's is at the top left
+				texcoords.push([v[0], 1.0 - *v.get(1).unwrap_or(&0.0)]);
+			},
+			"vn" => {
+				let v = floats(parts, num, 3, 3)?;
+				normals.push([v[0], v[1], v[2]]);
+			},
+			"f" => {
+				let mut face = Vec::new();
+				for corner in parts {
+					face.push(parse_corner(corner, num, positions.len(), texcoords.len(), normals.len())?);
+				}
+				if face.len() < 3 {
+					return Err(LoadError::parse(num, "face has fewer than three vertices"));
+				}
+				for j in 1..face.len() - 1 {
+					for &corner in &[face[0], face[j], face[j + 1]] {
+						builder.push(corner, &positions, &colors, &texcoords, &normals);
+					}
+				}
+			},
+			"o" | "g" => {
+				let name = parts.collect::<Vec<_>>().join(" ");
+				let material = builder.material.clone();
+				let old = ::std::mem::replace(&mut builder, Builder::new(name, material));
+				old.finish(&mut meshes);
+			},
+			"usemtl" => {
+				let name = parts.next().ok_or_else(|| LoadError::parse(num, "missing material name"))?;
+				let material = match materials.get(name) {
+					Some(m) => m.clone(),
+					None => Material::new(name.to_string()),
+				};
+				let old_name = builder.name.clone();
+				let old = ::std::mem::replace(&mut builder, Builder::new(old_name, Some(material)));
+				old.finish(&mut meshes);
+			},
+			"mtllib" => {
+				if let Some(base) = base {
+					for lib in parts {
+						materials.extend(load_mtl(base.join(lib))?);
+					}
+				}
+			},
+			// Smoothing groups and other statements don't affect the geometry
+			_ => {},
+		}
+	}
+	
+	builder.finish(&mut meshes);
+	Ok(meshes)
+}
+
+/// Loads the materials from an `.mtl` file, keyed by name
+pub fn load_mtl<P: AsRef<Path>>(path: P) -> Result<HashMap<String, Material>, LoadError> {
+	let path = path.as_ref();
+	let file = File::open(path)?;
+	parse_mtl(BufReader::new(file), path.parent())
+}
+
+/// Parses `.mtl` data. Texture paths are made relative to `base` if it is given.
+pub fn parse_mtl<R: BufRead>(reader: R, base: Option<&Path>) -> Result<HashMap<String, Material>, LoadError> {
+	let mut materials = HashMap::new();
+	let mut current: Option<Material> = None;
+	
+	for (i, line) in reader.lines().enumerate() {
+		let line = line?;
+		let num = i + 1;
+		let line = match line.find('#') {
+			Some(idx) => &line[..idx],
+			None => &line[..],
+		};
+		let mut parts = line.split_whitespace();
+		let keyword = match parts.next() {
+			Some(k) => k,
+			None => continue,
+		};
+		
+		if keyword == "newmtl" {
+			let name = parts.next().ok_or_else(|| LoadError::parse(num, "missing material name"))?;
+			if let Some(m) = current.take() {
+				materials.insert(m.name.clone(), m);
+			}
+			current = Some(Material::new(name.to_string()));
+			continue;
+		}
+		
+		let material = match current {
+			Some(ref mut m) => m,
+			None => return Err(LoadError::parse(num, format!("'{}' before any 'newmtl'", keyword))),
+		};
+		
+		match keyword {
+			"Kd" => {
+				let v = floats(parts, num, 3, 3)?;
+				let alpha = material.diffuse.raw()[3];
+				material.diffuse = Color::new(v[0], v[1], v[2], alpha);
+			},
+			"d" | "Tr" => {
+				let v = floats(parts, num, 1, 1)?;
+				let alpha = if keyword == "d" { v[0] } else { 1.0 - v[0] };
+				let c = material.diffuse.raw();
+				material.diffuse = Color::new(c[0], c[1], c[2], alpha);
+			},
+			"map_Kd" => {
+				// Options may precede the file name, which is always last
+				let file = parts.last().ok_or_else(|| LoadError::parse(num, "missing texture path"))?;
+				material.diffuse_map = Some(match base {
+					Some(base) => base.join(file),
+					None => PathBuf::from(file),
+				});
+			},
+			_ => {},
+		}
+	}
+	
+	if let Some(m) = current.take() {
+		materials.insert(m.name.clone(), m);
+	}
+	Ok(materials)
+}
+
+type Corner = (usize, Option<usize>, Option<usize>);
+
+struct Builder {
+	name: String,
+	material: Option<Material>,
+	data: MeshData,
+	lookup: HashMap<Corner, u32>,
+}
+
+impl Builder {
+	fn new(name: String, material: Option<Material>) -> Self {
+		Builder {
+			name,
+			material,
+			data: MeshData::default(),
+			lookup: HashMap::new(),
+		}
+	}
+	
+	fn push(&mut self, corner: Corner, positions: &[[f32; 4]], colors: &[Option<[f32; 4]>], texcoords: &[[f32; 2]], normals: &[[f32; 3]]) {
+		let data = &mut self.data;
+		let diffuse = self.material.as_ref().map(|m| m.diffuse).unwrap_or(Color::white());
+		let index = *self.lookup.entry(corner).or_insert_with(|| {
+			let (p, t, n) = corner;
+			data.verts.push(Vertex {
+				a_Pos: positions[p],
+				a_Color: colors[p].unwrap_or(diffuse.raw()),
+				a_Normal: n.map(|n| normals[n]).unwrap_or([0.0, 0.0, 0.0]),
+				a_TexCoord: t.map(|t| texcoords[t]).unwrap_or([0.0, 0.0]),
+				a_Tangent: [0.0, 0.0, 0.0, 0.0],
+			});
+			(data.verts.len() - 1) as u32
+		});
+		data.indices.push(index);
+	}
+	
+	fn finish(self, meshes: &mut Vec<ObjMesh>) {
+		if !self.data.indices.is_empty() {
+			meshes.push(ObjMesh {
+				name: self.name,
+				material: self.material,
+				data: self.data,
+			});
+		}
+	}
+}
+
+fn floats(parts: SplitWhitespace, line: usize, min: usize, max: usize) -> Result<Vec<f32>, LoadError> {
+	let values = parts
+		.map(|p| p.parse::<f32>().map_err(|_| LoadError::parse(line, format!("invalid number '{}'", p))))
+		.collect::<Result<Vec<_>, _>>()?;
+	if values.len() < min || values.len() > max {
+		return Err(LoadError::parse(line, format!("expected {} to {} values, found {}", min, max, values.len())));
+	}
+	Ok(values)
+}
+
+fn parse_corner(corner: &str, line: usize, positions: usize, texcoords: usize, normals: usize) -> Result<Corner, LoadError> {
+	let mut parts = corner.split('/');
+	let p = match parts.next() {
+		Some(p) if !p.is_empty() => resolve(p, positions, line)?,
+		_ => return Err(LoadError::parse(line, format!("missing position index in '{}'", corner))),
+	};
+	let t = match parts.next() {
+		Some(t) if !t.is_empty() => Some(resolve(t, texcoords, line)?),
+		_ => None,
+	};
+	let n = match parts.next() {
+		Some(n) if !n.is_empty() => Some(resolve(n, normals, line)?),
+		_ => None,
+	};
+	Ok((p, t, n))
+}
+
+/// Turns a 1-based (or negative, relative) OBJ index into a 0-based one
+fn resolve(index: &str, count: usize, line: usize) -> Result<usize, LoadError> {
+	let i = index.parse::<i64>().map_err(|_| LoadError::parse(line, format!("invalid index '{}'", index)))?;
+	let resolved = if i > 0 {
+		i - 1
+	} else {
+		count as i64 + i
+	};
+	if i == 0 || resolved < 0 || resolved >= count as i64 {
+		return Err(LoadError::parse(line, format!("index {} out of range", i)));
+	}
+	Ok(resolved as usize)
+}
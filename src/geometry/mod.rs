@@ -0,0 +1,15 @@
+//! CPU-side processing of `MeshData` before it is uploaded as a `Mesh`
+
+mod weld;
+mod normals;
+mod tangents;
+pub mod optimize;
+mod simplify;
+
+pub use self::weld::{weld, weld_epsilon, WeldReport};
+pub use self::normals::{generate_normals, flat_normals, NormalMode, Weighting};
+pub use self::tangents::generate_tangents;
+pub(crate) use self::tangents::generate_tangents_mapped;
+pub use self::optimize::optimize;
+pub(crate) use self::optimize::optimize_mapped;
+pub use self::simplify::{simplify, lod_chain};
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use cgmath::{Vector3, InnerSpace, Zero, Deg, Rad, Angle};
+
+use obj::MeshData;
+use geometry::weld;
+
+/// How the normals of the faces around a vertex are weighted when averaging them
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Weighting {
+	/// Larger faces pull the normal further towards their own
+	Area,
+	/// Faces are weighted by the angle of their corner at the vertex, which doesn't depend on
+	/// how the surface is tessellated
+	Angle,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NormalMode {
+	/// Every triangle gets its own three vertices carrying the face normal
+	Flat,
+	/// Faces meeting at a position are averaged, unless the angle between them is larger than
+	/// `crease`, in which case the vertex is split to keep a hard edge
+	Smooth { weighting: Weighting, crease: Option<Deg<f32>> },
+}
+
+/// Computes new normals for the mesh, adding vertices where `mode` needs to split them. Data
+/// without any triangles, such as a point cloud, is left alone.
+pub fn generate_normals(data: &mut MeshData, mode: NormalMode) {
+	if data.indices.len() < 3 {
+		return;
+	}
+	match mode {
+		NormalMode::Flat => flat_normals(data),
+		NormalMode::Smooth { weighting, crease } => creased_normals(data, weighting, crease),
+	}
+}
+
+/// Gives every triangle its own vertices so they can all carry the face normal
+pub fn flat_normals(data: &mut MeshData) {
+	let mut verts = Vec::with_capacity(data.indices.len());
+	
+	for tri in data.indices.chunks(3) {
+		if tri.len() < 3 {
+			break;
+		}
+		let corners = [data.verts[tri[0] as usize], data.verts[tri[1] as usize], data.verts[tri[2] as usize]];
+		let n = normalize_or_zero((corners[1].vec3() - corners[0].vec3()).cross(corners[2].vec3() - corners[0].vec3()));
+		for mut v in corners.iter().cloned() {
+			v.a_Normal = n;
+			verts.push(v);
+		}
+	}
+	
+	data.indices = (0..verts.len() as u32).collect();
+	data.verts = verts;
+}
+
+fn creased_normals(data: &mut MeshData, weighting: Weighting, crease: Option<Deg<f32>>) {
+	let tris = data.indices.len() / 3;
+	let cos_crease = crease.map(|c| Rad::from(c).cos());
+	
+	// Unit face normals, and the weight of each corner
+	let mut faces = Vec::with_capacity(tris);
+	let mut weights = Vec::with_capacity(tris * 3);
+	for tri in data.indices.chunks(3).take(tris) {
+		let p = [data.verts[tri[0] as usize].vec3(), data.verts[tri[1] as usize].vec3(), data.verts[tri[2] as usize].vec3()];
+		let n = (p[1] - p[0]).cross(p[2] - p[0]);
+		faces.push(if n.magnitude2() > 0.0 { n.normalize() } else { n });
+		for i in 0..3 {
+			weights.push(match weighting {
+				Weighting::Area => n.magnitude(),
+				Weighting::Angle => {
+					let (e1, e2) = (p[(i + 1) % 3] - p[i], p[(i + 2) % 3] - p[i]);
+					if e1.magnitude2() > 0.0 && e2.magnitude2() > 0.0 {
+						e1.normalize().dot(e2.normalize()).max(-1.0).min(1.0).acos()
+					} else {
+						0.0
+					}
+				},
+			});
+		}
+	}
+	
+	// Corners are smoothed with every other corner at the same position, even across vertices
+	// that aren't shared in the index buffer
+	let mut groups: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+	for (corner, &index) in data.indices.iter().take(tris * 3).enumerate() {
+		let p = data.verts[index as usize].a_Pos;
+		groups.entry([weld::bits(p[0]), weld::bits(p[1]), weld::bits(p[2])]).or_insert_with(Vec::new).push(corner);
+	}
+	
+	let mut normals = vec![[0.0; 3]; tris * 3];
+	for corners in groups.values() {
+		for &corner in corners {
+			let face = faces[corner / 3];
+			let mut sum: Vector3<f32> = Vector3::zero();
+			for &other in corners {
+				let other_face = faces[other / 3];
+				let smooth = match cos_crease {
+					Some(cos) => face.dot(other_face) >= cos,
+					None => true,
+				};
+				if smooth {
+					sum += other_face * weights[other];
+				}
+			}
+			normals[corner] = normalize_or_zero(sum);
+		}
+	}
+	
+	// Corners that ended up with identical vertices are shared again, the rest are split
+	let mut lookup: HashMap<weld::Key, u32> = HashMap::new();
+	let mut verts = Vec::with_capacity(data.verts.len());
+	let mut indices = Vec::with_capacity(tris * 3);
+	for (corner, &index) in data.indices.iter().take(tris * 3).enumerate() {
+		let mut v = data.verts[index as usize];
+		v.a_Normal = normals[corner];
+		let new_index = *lookup.entry(weld::key(&v)).or_insert_with(|| {
+			verts.push(v);
+			(verts.len() - 1) as u32
+		});
+		indices.push(new_index);
+	}
+	
+	data.verts = verts;
+	data.indices = indices;
+}
+
+fn normalize_or_zero(n: Vector3<f32>) -> [f32; 3] {
+	if n.magnitude2() > 0.0 {
+		*n.normalize().as_ref()
+	} else {
+		[0.0, 0.0, 0.0]
+	}
+}
@@ -0,0 +1,261 @@
+//! Reordering of triangle lists for faster rendering, in the spirit of meshoptimizer.
+//!
+//! None of these change what is drawn, only the order it is drawn in and the order vertices are
+//! stored in. They're meant to run in the order `optimize` runs them.
+
+use std::cmp::Ordering;
+
+use cgmath::{Vector3, InnerSpace, Zero};
+
+use obj::MeshData;
+
+const CACHE_SIZE: usize = 32;
+
+/// Runs every optimization in the recommended order: vertex cache, then overdraw, then fetch.
+/// Data without indices is left alone, and indices that don't make up whole triangles only have
+/// their vertices reordered.
+pub fn optimize(data: &mut MeshData) {
+	optimize_mapped(data);
+}
+
+/// Like `optimize`, but also returns the index each vertex had before, so data kept alongside the
+/// vertices can follow them
+pub(crate) fn optimize_mapped(data: &mut MeshData) -> Vec<u32> {
+	if data.indices.is_empty() {
+		return (0..data.verts.len() as u32).collect();
+	}
+	if data.indices.len() % 3 == 0 {
+		let count = data.verts.len();
+		optimize_vertex_cache(&mut data.indices, count);
+		optimize_overdraw(data);
+	}
+	vertex_fetch_mapped(data)
+}
+
+/// Reorders triangles so that vertices are reused while they're still in the GPU's
+/// post-transform cache, using Tom Forsyth's linear-speed algorithm
+pub fn optimize_vertex_cache(indices: &mut [u32], vertex_count: usize) {
+	let tris = indices.len() / 3;
+	if tris == 0 {
+		return;
+	}
+	
+	// Triangles adjacent to each vertex, stored contiguously
+	let mut remaining = vec![0u32; vertex_count];
+	for &i in &indices[..tris * 3] {
+		remaining[i as usize] += 1;
+	}
+	let mut offsets = Vec::with_capacity(vertex_count + 1);
+	let mut total = 0;
+	for &r in &remaining {
+		offsets.push(total);
+		total += r as usize;
+	}
+	offsets.push(total);
+	let mut adjacency = vec![0u32; total];
+	let mut fill = offsets.clone();
+	for (t, tri) in indices.chunks(3).take(tris).enumerate() {
+		for &i in tri {
+			adjacency[fill[i as usize]] = t as u32;
+			fill[i as usize] += 1;
+		}
+	}
+	
+	let mut cache_pos: Vec<i32> = vec![-1; vertex_count];
+	let mut vertex_score: Vec<f32> = (0..vertex_count).map(|v| score(-1, remaining[v])).collect();
+	let mut added = vec![false; tris];
+	let mut tri_score: Vec<f32> = indices.chunks(3).take(tris)
+		.map(|tri| tri.iter().map(|&i| vertex_score[i as usize]).sum())
+		.collect();
+	
+	let mut output = Vec::with_capacity(tris * 3);
+	let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+	let mut best = best_triangle(&tri_score, &added, 0);
+	let mut scan = 0;
+	
+	while let Some(t) = best {
+		added[t] = true;
+		let tri = [indices[t * 3], indices[t * 3 + 1], indices[t * 3 + 2]];
+		output.extend_from_slice(&tri);
+		
+		for &v in &tri {
+			let v = v as usize;
+			remaining[v] -= 1;
+			// Move the finished triangle to the end of the vertex's live range
+			let (start, end) = (offsets[v], offsets[v] + remaining[v] as usize + 1);
+			if let Some(pos) = adjacency[start..end].iter().position(|&a| a == t as u32) {
+				adjacency.swap(start + pos, end - 1);
+			}
+		}
+		
+		// The triangle's vertices move to the front of the cache, pushing the oldest ones out
+		let mut new_cache = tri.to_vec();
+		new_cache.extend(cache.iter().cloned().filter(|v| !tri.contains(v)));
+		for &v in new_cache.iter().skip(CACHE_SIZE) {
+			cache_pos[v as usize] = -1;
+		}
+		for (pos, &v) in new_cache.iter().enumerate().take(CACHE_SIZE) {
+			cache_pos[v as usize] = pos as i32;
+		}
+		
+		// Only triangles touching the touched vertices can change score
+		best = None;
+		let mut best_score = -1.0;
+		for &v in &new_cache {
+			let v = v as usize;
+			vertex_score[v] = score(cache_pos[v], remaining[v]);
+		}
+		for &v in &new_cache {
+			let v = v as usize;
+			for &a in &adjacency[offsets[v]..offsets[v] + remaining[v] as usize] {
+				let a = a as usize;
+				let s = vertex_score[indices[a * 3] as usize]
+					+ vertex_score[indices[a * 3 + 1] as usize]
+					+ vertex_score[indices[a * 3 + 2] as usize];
+				tri_score[a] = s;
+				if s > best_score {
+					best_score = s;
+					best = Some(a);
+				}
+			}
+		}
+		new_cache.truncate(CACHE_SIZE);
+		cache = new_cache;
+		
+		if best.is_none() {
+			// Nothing in the cache has triangles left, so start somewhere new
+			while scan < tris && added[scan] {
+				scan += 1;
+			}
+			best = best_triangle(&tri_score, &added, scan);
+		}
+	}
+	
+	indices[..tris * 3].copy_from_slice(&output);
+}
+
+fn score(cache_pos: i32, remaining: u32) -> f32 {
+	if remaining == 0 {
+		return -1.0;
+	}
+	let cache = if cache_pos < 0 {
+		0.0
+	} else if cache_pos < 3 {
+		// The last triangle's vertices get a fixed score so its neighbours aren't preferred too much
+		0.75
+	} else {
+		(1.0 - (cache_pos - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(1.5)
+	};
+	// Vertices with few triangles left are finished first so they can leave the cache
+	cache + 2.0 * (remaining as f32).powf(-0.5)
+}
+
+fn best_triangle(scores: &[f32], added: &[bool], from: usize) -> Option<usize> {
+	let mut best = None;
+	let mut best_score = ::std::f32::NEG_INFINITY;
+	for t in from..scores.len() {
+		if !added[t] && scores[t] > best_score {
+			best_score = scores[t];
+			best = Some(t);
+		}
+	}
+	best
+}
+
+/// Sorts groups of triangles so that outward facing ones on the outside of the mesh are drawn
+/// first, letting early depth testing reject more of what's behind them. Groups are split where
+/// the vertex cache would have to start over anyway, so this keeps most of the benefit of
+/// `optimize_vertex_cache`, which should be run first.
+pub fn optimize_overdraw(data: &mut MeshData) {
+	let tris = data.indices.len() / 3;
+	if tris == 0 {
+		return;
+	}
+	
+	// Split wherever a triangle misses the (simulated, FIFO) cache on all three vertices
+	let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE);
+	let mut starts = vec![0];
+	for (t, tri) in data.indices.chunks(3).take(tris).enumerate() {
+		let misses = tri.iter().filter(|&&i| !cache.contains(&i)).count();
+		if misses == 3 && t != 0 {
+			starts.push(t);
+		}
+		for &i in tri {
+			if !cache.contains(&i) {
+				if cache.len() == CACHE_SIZE {
+					cache.remove(0);
+				}
+				cache.push(i);
+			}
+		}
+	}
+	starts.push(tris);
+	
+	let pos = |i: u32| data.verts[i as usize].vec3();
+	let mut center: Vector3<f32> = Vector3::zero();
+	let mut area = 0.0;
+	let mut clusters = Vec::with_capacity(starts.len() - 1);
+	for range in starts.windows(2) {
+		let mut c_center: Vector3<f32> = Vector3::zero();
+		let mut c_normal: Vector3<f32> = Vector3::zero();
+		let mut c_area = 0.0;
+		for tri in data.indices[range[0] * 3..range[1] * 3].chunks(3) {
+			let (a, b, c) = (pos(tri[0]), pos(tri[1]), pos(tri[2]));
+			let n = (b - a).cross(c - a);
+			let tri_area = n.magnitude();
+			c_center += (a + b + c) / 3.0 * tri_area;
+			c_normal += n;
+			c_area += tri_area;
+		}
+		center += c_center;
+		area += c_area;
+		clusters.push((range[0], range[1], c_center, c_normal, c_area));
+	}
+	if area > 0.0 {
+		center /= area;
+	}
+	
+	let mut keyed = clusters.into_iter().map(|(start, end, c_center, c_normal, c_area)| {
+		let c_center = if c_area > 0.0 { c_center / c_area } else { c_center };
+		let normal = if c_normal.magnitude2() > 0.0 { c_normal.normalize() } else { c_normal };
+		((c_center - center).dot(normal), start, end)
+	}).collect::<Vec<_>>();
+	keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+	
+	let mut indices = Vec::with_capacity(data.indices.len());
+	for &(_, start, end) in &keyed {
+		indices.extend_from_slice(&data.indices[start * 3..end * 3]);
+	}
+	indices.extend_from_slice(&data.indices[tris * 3..]);
+	data.indices = indices;
+}
+
+/// Reorders vertices to the order the index buffer first uses them in, so vertex fetches walk
+/// memory linearly. Vertices that aren't used at all are moved to the end.
+pub fn optimize_vertex_fetch(data: &mut MeshData) {
+	vertex_fetch_mapped(data);
+}
+
+fn vertex_fetch_mapped(data: &mut MeshData) -> Vec<u32> {
+	if data.indices.is_empty() {
+		return (0..data.verts.len() as u32).collect();
+	}
+	let mut remap = vec![::std::u32::MAX; data.verts.len()];
+	let mut sources = Vec::with_capacity(data.verts.len());
+	
+	for i in &mut data.indices {
+		let old = *i as usize;
+		if remap[old] == ::std::u32::MAX {
+			remap[old] = sources.len() as u32;
+			sources.push(old as u32);
+		}
+		*i = remap[old];
+	}
+	for (old, &new) in remap.iter().enumerate() {
+		if new == ::std::u32::MAX {
+			sources.push(old as u32);
+		}
+	}
+	data.verts = sources.iter().map(|&i| data.verts[i as usize]).collect();
+	sources
+}
@@ -0,0 +1,383 @@
+//! Mesh simplification by quadric error metric edge collapse (Garland & Heckbert).
+//!
+//! Collapses work on positions, so the vertices stored at the same position (on an attribute seam,
+//! or in a mesh that isn't welded) always move together. Each of them turns into the vertex it
+//! shares a collapsing triangle with, which lets seams collapse along themselves and keeps the
+//! attributes on either side intact. Vertices without such a partner keep their attributes at the
+//! new position. Open borders and seams are kept in place by extra planes perpendicular to them.
+
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::cmp::Ordering;
+
+use cgmath::{Vector3, InnerSpace};
+
+use obj::MeshData;
+use td::Vertex;
+use geometry::weld;
+
+/// Reduces the mesh to at most `target_triangles` triangles, unless that would move the surface
+/// further than `max_error` away from the original. The distance is in the mesh's units, taken as
+/// the root mean square distance from a moved position to the planes of the original triangles
+/// around it, with the planes keeping borders and seams in place weighing a thousand times more.
+/// Pass infinity as `max_error` to only stop at the target. Mesh data without indices is
+/// simplified as a triangle list in vertex order.
+pub fn simplify(data: &MeshData, target_triangles: usize, max_error: f32) -> MeshData {
+	Simplifier::new(data).run(target_triangles, max_error as f64)
+}
+
+/// Builds successively simpler versions of a mesh, one for each ratio of the original triangle
+/// count. Ratios should be decreasing, each level is simplified from the previous one.
+pub fn lod_chain(data: &MeshData, ratios: &[f32], max_error: f32) -> Vec<MeshData> {
+	let tris = data.triangle_indices().len() / 3;
+	let mut levels: Vec<MeshData> = Vec::with_capacity(ratios.len());
+	for &ratio in ratios {
+		let target = (tris as f32 * ratio.max(0.0).min(1.0)) as usize;
+		let next = simplify(levels.last().unwrap_or(data), target, max_error);
+		levels.push(next);
+	}
+	levels
+}
+
+/// A symmetric 4x4 matrix stored as its upper triangle, followed by the total weight of the planes
+/// added into it
+#[derive(Copy, Clone, Default)]
+struct Quadric([f64; 11]);
+
+impl Quadric {
+	fn plane(n: Vector3<f64>, d: f64, weight: f64) -> Self {
+		let (a, b, c) = (n.x, n.y, n.z);
+		Quadric([a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d, 1.0]).scale(weight)
+	}
+	
+	fn scale(self, s: f64) -> Self {
+		let mut q = self.0;
+		for v in q.iter_mut() {
+			*v *= s;
+		}
+		Quadric(q)
+	}
+	
+	fn add(&mut self, other: &Quadric) {
+		for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+			*a += *b;
+		}
+	}
+	
+	/// The weighted mean of the squared distances from `p` to the planes this quadric was built from
+	fn error(&self, p: Vector3<f64>) -> f64 {
+		let q = &self.0;
+		let (x, y, z) = (p.x, p.y, p.z);
+		let e = q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+			+ q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+			+ q[7] * z * z + 2.0 * q[8] * z
+			+ q[9];
+		if q[10] > 0.0 { e.max(0.0) / q[10] } else { 0.0 }
+	}
+}
+
+/// A candidate collapse of `from` onto `to`, ordered so the heap pops the cheapest first
+struct Collapse {
+	cost: f64,
+	from: u32,
+	to: u32,
+	versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+	fn eq(&self, other: &Self) -> bool {
+		self.cost == other.cost
+	}
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Collapse {
+	fn cmp(&self, other: &Self) -> Ordering {
+		other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+	}
+}
+
+struct Simplifier {
+	/// The original vertices, followed by copies made when a collapse moves a vertex
+	verts: Vec<Vertex>,
+	/// The position each vertex is at
+	position_of: Vec<u32>,
+	/// Vertices with the same class have the same attributes apart from their position
+	class_of: Vec<u32>,
+	positions: Vec<Vector3<f64>>,
+	tris: Vec<[u32; 3]>,
+	alive: Vec<bool>,
+	/// Triangles around each position, including dead ones which are skipped
+	adjacency: Vec<Vec<usize>>,
+	quadrics: Vec<Quadric>,
+	removed: Vec<bool>,
+	versions: Vec<u32>,
+	heap: BinaryHeap<Collapse>,
+}
+
+impl Simplifier {
+	fn new(data: &MeshData) -> Self {
+		let mut by_position: HashMap<[u32; 3], u32> = HashMap::new();
+		let mut positions = Vec::new();
+		let position_of = data.verts.iter().map(|v| {
+			let key = [weld::bits(v.a_Pos[0]), weld::bits(v.a_Pos[1]), weld::bits(v.a_Pos[2])];
+			let next = positions.len() as u32;
+			*by_position.entry(key).or_insert_with(|| {
+				positions.push(Vector3::new(v.a_Pos[0] as f64, v.a_Pos[1] as f64, v.a_Pos[2] as f64));
+				next
+			})
+		}).collect::<Vec<_>>();
+		let mut by_class: HashMap<weld::Key, u32> = HashMap::new();
+		let class_of = data.verts.iter().map(|v| {
+			let mut attributes = *v;
+			attributes.a_Pos = [0.0, 0.0, 0.0, v.a_Pos[3]];
+			let next = by_class.len() as u32;
+			*by_class.entry(weld::key(&attributes)).or_insert(next)
+		}).collect::<Vec<_>>();
+		
+		let tris = data.triangle_indices().chunks(3)
+			.filter(|t| t.len() == 3)
+			.map(|t| [t[0], t[1], t[2]])
+			.collect::<Vec<_>>();
+		let count = positions.len();
+		
+		let mut adjacency = vec![Vec::new(); count];
+		for (t, tri) in tris.iter().enumerate() {
+			for &i in tri {
+				adjacency[position_of[i as usize] as usize].push(t);
+			}
+		}
+		
+		// The classes each triangle has at the ends of each of its edges
+		let mut quadrics = vec![Quadric::default(); count];
+		let mut edges: HashMap<(u32, u32), Vec<(u32, u32)>> = HashMap::new();
+		for tri in &tris {
+			let at = [position_of[tri[0] as usize], position_of[tri[1] as usize], position_of[tri[2] as usize]];
+			let p = [positions[at[0] as usize], positions[at[1] as usize], positions[at[2] as usize]];
+			let n = (p[1] - p[0]).cross(p[2] - p[0]);
+			if n.magnitude2() == 0.0 {
+				continue;
+			}
+			let n = n.normalize();
+			let q = Quadric::plane(n, -n.dot(p[0]), 1.0);
+			for &i in &at {
+				quadrics[i as usize].add(&q);
+			}
+			for k in 0..3 {
+				let (a, b) = (tri[k], tri[(k + 1) % 3]);
+				let (ca, cb) = (class_of[a as usize], class_of[b as usize]);
+				let (a, b) = (at[k], at[(k + 1) % 3]);
+				edges.entry(if a < b { (a, b) } else { (b, a) }).or_insert_with(Vec::new)
+					.push(if a < b { (ca, cb) } else { (cb, ca) });
+			}
+		}
+		
+		// Edges used by only one triangle are borders, and edges with different attributes on
+		// either side are seams. Both get a heavily weighted plane through the edge and
+		// perpendicular to the face, so borders don't shrink and seams only move along themselves.
+		for tri in &tris {
+			let at = [position_of[tri[0] as usize], position_of[tri[1] as usize], position_of[tri[2] as usize]];
+			let p = [positions[at[0] as usize], positions[at[1] as usize], positions[at[2] as usize]];
+			let n = (p[1] - p[0]).cross(p[2] - p[0]);
+			for k in 0..3 {
+				let (a, b) = (at[k], at[(k + 1) % 3]);
+				let sides = match edges.get(&if a < b { (a, b) } else { (b, a) }) {
+					Some(sides) => sides,
+					None => continue,
+				};
+				if sides.len() != 1 && sides.iter().all(|&s| s == sides[0]) {
+					continue;
+				}
+				let edge = p[(k + 1) % 3] - p[k];
+				let side = edge.cross(n);
+				if side.magnitude2() == 0.0 {
+					continue;
+				}
+				let side = side.normalize();
+				let q = Quadric::plane(side, -side.dot(p[k]), 1000.0);
+				quadrics[a as usize].add(&q);
+				quadrics[b as usize].add(&q);
+			}
+		}
+		
+		Simplifier {
+			verts: data.verts.clone(),
+			position_of,
+			class_of,
+			positions,
+			alive: vec![true; tris.len()],
+			tris,
+			adjacency,
+			quadrics,
+			removed: vec![false; count],
+			versions: vec![0; count],
+			heap: BinaryHeap::new(),
+		}
+	}
+	
+	/// The position each corner of triangle `t` is at
+	fn corners(&self, t: usize) -> [u32; 3] {
+		let tri = self.tris[t];
+		[self.position_of[tri[0] as usize], self.position_of[tri[1] as usize], self.position_of[tri[2] as usize]]
+	}
+	
+	fn neighbours(&self, p: u32) -> HashSet<u32> {
+		let mut out = HashSet::new();
+		for &t in &self.adjacency[p as usize] {
+			if self.alive[t] {
+				out.extend(self.corners(t).iter().cloned().filter(|&i| i != p));
+			}
+		}
+		out
+	}
+	
+	fn push(&mut self, from: u32, to: u32) {
+		let mut q = self.quadrics[from as usize];
+		q.add(&self.quadrics[to as usize]);
+		self.heap.push(Collapse {
+			cost: q.error(self.positions[to as usize]),
+			from,
+			to,
+			versions: (self.versions[from as usize], self.versions[to as usize]),
+		});
+	}
+	
+	/// Whether moving `from` onto `to` would turn any of the remaining triangles around
+	fn flips(&self, from: u32, to: u32) -> bool {
+		let target = self.positions[to as usize];
+		for &t in &self.adjacency[from as usize] {
+			let at = self.corners(t);
+			if !self.alive[t] || at.contains(&to) {
+				continue;
+			}
+			let p = [self.positions[at[0] as usize], self.positions[at[1] as usize], self.positions[at[2] as usize]];
+			let before = (p[1] - p[0]).cross(p[2] - p[0]);
+			let moved = [0, 1, 2].iter()
+				.map(|&k| if at[k] == from { target } else { p[k] })
+				.collect::<Vec<_>>();
+			let after = (moved[1] - moved[0]).cross(moved[2] - moved[0]);
+			if before.dot(after) <= 0.0 {
+				return true;
+			}
+		}
+		false
+	}
+	
+	/// Moves every vertex at `from` onto `to`, removing the triangles between them
+	fn collapse(&mut self, from: u32, to: u32) -> usize {
+		let adjacent = ::std::mem::replace(&mut self.adjacency[from as usize], Vec::new());
+		
+		// A vertex turns into the one it shares a collapsing triangle with
+		let mut partners: HashMap<u32, u32> = HashMap::new();
+		let mut dead = 0;
+		for &t in &adjacent {
+			let at = self.corners(t);
+			if !self.alive[t] || !at.contains(&to) {
+				continue;
+			}
+			let tri = self.tris[t];
+			let (a, b) = (at.iter().position(|&p| p == from).unwrap(), at.iter().position(|&p| p == to).unwrap());
+			partners.entry(self.class_of[tri[a] as usize]).or_insert(tri[b]);
+			self.alive[t] = false;
+			dead += 1;
+		}
+		
+		let target = self.positions[to as usize];
+		for t in adjacent {
+			if !self.alive[t] {
+				continue;
+			}
+			for k in 0..3 {
+				let v = self.tris[t][k] as usize;
+				if self.position_of[v] != from {
+					continue;
+				}
+				let class = self.class_of[v];
+				let moved = match partners.get(&class) {
+					Some(&moved) => moved,
+					None => {
+						let mut copy = self.verts[v];
+						copy.a_Pos = [target.x as f32, target.y as f32, target.z as f32, copy.a_Pos[3]];
+						self.verts.push(copy);
+						self.position_of.push(to);
+						self.class_of.push(class);
+						(self.verts.len() - 1) as u32
+					},
+				};
+				partners.insert(class, moved);
+				self.tris[t][k] = moved;
+			}
+			self.adjacency[to as usize].push(t);
+		}
+		
+		let q = self.quadrics[from as usize];
+		self.quadrics[to as usize].add(&q);
+		self.removed[from as usize] = true;
+		self.versions[to as usize] += 1;
+		dead
+	}
+	
+	fn run(mut self, target: usize, max_error: f64) -> MeshData {
+		let mut live = self.tris.len();
+		for p in 0..self.positions.len() as u32 {
+			for n in self.neighbours(p) {
+				self.push(p, n);
+			}
+		}
+		
+		let max_cost = max_error * max_error;
+		while live > target {
+			let c = match self.heap.pop() {
+				Some(c) => c,
+				None => break,
+			};
+			let (from, to) = (c.from as usize, c.to as usize);
+			if self.removed[from] || self.removed[to] || c.versions != (self.versions[from], self.versions[to]) {
+				continue;
+			}
+			if c.cost > max_cost {
+				break;
+			}
+			if self.flips(c.from, c.to) {
+				continue;
+			}
+			
+			live -= self.collapse(c.from, c.to);
+			for n in self.neighbours(c.to) {
+				self.versions[n as usize] += 1;
+			}
+			for n in self.neighbours(c.to) {
+				self.push(c.to, n);
+				self.push(n, c.to);
+				for m in self.neighbours(n) {
+					if m != c.to {
+						self.push(n, m);
+					}
+				}
+			}
+		}
+		
+		// Keep only the surviving triangles and the vertices they use
+		let mut remap = vec![::std::u32::MAX; self.verts.len()];
+		let mut out = MeshData::default();
+		for (t, tri) in self.tris.iter().enumerate() {
+			if !self.alive[t] {
+				continue;
+			}
+			for &i in tri {
+				if remap[i as usize] == ::std::u32::MAX {
+					remap[i as usize] = out.verts.len() as u32;
+					out.verts.push(self.verts[i as usize]);
+				}
+				out.indices.push(remap[i as usize]);
+			}
+		}
+		out
+	}
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use cgmath::{Vector3, InnerSpace, Zero};
+
+use obj::MeshData;
+use td::Vertex;
+use geometry::weld;
+
+/// Computes per-vertex tangents from the texture coordinates of an indexed triangle mesh, the way
+/// MikkTSpace does so that normal maps baked against it line up.
+///
+/// Vertices with the same position, normal and texture coordinates are treated as one. The
+/// triangles around such a vertex that are connected through shared edges and have the same
+/// texture winding form a group, which gets a single tangent: the average of each triangle's
+/// texture-space direction projected onto the vertex normal, weighted by the triangle's angle at
+/// the vertex. `w` holds the bitangent sign, so the bitangent is `cross(normal, tangent.xyz) * w`.
+/// Vertices that end up in several groups are split. Normals must already be present.
+///
+/// Unlike the reference implementation, groups aren't split further when their tangents diverge,
+/// which the reference only does when given an angular threshold below its default.
+pub fn generate_tangents(data: &mut MeshData) {
+	generate_tangents_mapped(data);
+}
+
+/// Like `generate_tangents`, but also returns the index each vertex had before, so data kept
+/// alongside the vertices can follow them
+pub(crate) fn generate_tangents_mapped(data: &mut MeshData) -> Vec<u32> {
+	let tris = data.indices.len() / 3;
+	data.indices.truncate(tris * 3);
+	let verts = &data.verts;
+	let indices = &data.indices;
+	
+	// Corners refer to vertices by value, so that duplicates in the index buffer don't matter
+	let mut by_value: HashMap<[u32; 8], u32> = HashMap::new();
+	let shared = indices.iter().map(|&i| {
+		let next = by_value.len() as u32;
+		*by_value.entry(value_key(&verts[i as usize])).or_insert(next)
+	}).collect::<Vec<_>>();
+	
+	let mut faces = indices.chunks(3).map(|tri| face(&verts[tri[0] as usize], &verts[tri[1] as usize], &verts[tri[2] as usize])).collect::<Vec<_>>();
+	
+	// Each edge is shared with the triangle that has it the other way around
+	let mut edges: HashMap<(u32, u32), usize> = HashMap::new();
+	for f in 0..tris {
+		for i in 0..3 {
+			edges.entry((shared[f * 3 + i], shared[f * 3 + (i + 1) % 3])).or_insert(f);
+		}
+	}
+	let neighbour = |f: usize, i: usize| edges.get(&(shared[f * 3 + (i + 1) % 3], shared[f * 3 + i])).cloned();
+	
+	// Triangles without a texture mapping take the winding of a neighbour
+	for f in 0..tris {
+		if faces[f].degenerate {
+			if let Some(n) = (0..3).filter_map(|i| neighbour(f, i)).find(|&n| !faces[n].degenerate) {
+				faces[f].preserving = faces[n].preserving;
+			}
+		}
+	}
+	
+	// Corners of the same vertex in neighbouring triangles with the same winding share a group
+	let mut groups = (0..tris * 3).collect::<Vec<_>>();
+	for f in 0..tris {
+		for i in 0..3 {
+			let n = match neighbour(f, i) {
+				Some(n) if faces[n].preserving == faces[f].preserving => n,
+				_ => continue,
+			};
+			for &corner in &[f * 3 + i, f * 3 + (i + 1) % 3] {
+				if let Some(other) = (n * 3..n * 3 + 3).find(|&c| shared[c] == shared[corner]) {
+					union(&mut groups, corner, other);
+				}
+			}
+		}
+	}
+	
+	let mut sums: HashMap<usize, Vector3<f32>> = HashMap::new();
+	for corner in 0..tris * 3 {
+		let (f, i) = (corner / 3, corner % 3);
+		let p = |k: usize| verts[indices[f * 3 + k % 3] as usize].vec3();
+		let n = Vector3::from(verts[indices[corner] as usize].a_Normal);
+		let s = project(n, faces[f].s);
+		let (e1, e2) = (project(n, p(i + 2) - p(i)), project(n, p(i + 1) - p(i)));
+		let angle = e1.dot(e2).max(-1.0).min(1.0).acos();
+		*sums.entry(find(&mut groups, corner)).or_insert_with(Vector3::zero) += s * angle;
+	}
+	
+	let mut lookup: HashMap<(u32, [u32; 4]), u32> = HashMap::new();
+	let mut out = Vec::with_capacity(verts.len());
+	let mut sources = Vec::with_capacity(verts.len());
+	let mut out_indices = Vec::with_capacity(tris * 3);
+	for corner in 0..tris * 3 {
+		let index = indices[corner];
+		let mut v = verts[index as usize];
+		let n = Vector3::from(v.a_Normal);
+		let t = perpendicular(n, sums[&find(&mut groups, corner)]);
+		let w = if faces[corner / 3].preserving { 1.0 } else { -1.0 };
+		v.a_Tangent = [t.x, t.y, t.z, w];
+		let key = (index, [t.x.to_bits(), t.y.to_bits(), t.z.to_bits(), w.to_bits()]);
+		let new_index = *lookup.entry(key).or_insert_with(|| {
+			out.push(v);
+			sources.push(index);
+			(out.len() - 1) as u32
+		});
+		out_indices.push(new_index);
+	}
+	
+	data.verts = out;
+	data.indices = out_indices;
+	sources
+}
+
+struct Face {
+	/// The direction the first texture coordinate increases in, or zero without a mapping
+	s: Vector3<f32>,
+	/// Whether the texture coordinates wind the same way as the positions
+	preserving: bool,
+	degenerate: bool,
+}
+
+fn face(a: &Vertex, b: &Vertex, c: &Vertex) -> Face {
+	let (d1, d2) = (b.vec3() - a.vec3(), c.vec3() - a.vec3());
+	let (du1, dv1) = (b.a_TexCoord[0] - a.a_TexCoord[0], b.a_TexCoord[1] - a.a_TexCoord[1]);
+	let (du2, dv2) = (c.a_TexCoord[0] - a.a_TexCoord[0], c.a_TexCoord[1] - a.a_TexCoord[1]);
+	let area = du1 * dv2 - dv1 * du2;
+	let s = d1 * dv2 - d2 * dv1;
+	let degenerate = area.abs() <= ::std::f32::MIN_POSITIVE;
+	Face {
+		s: if degenerate || s.magnitude2() == 0.0 { Vector3::zero() } else { s.normalize() * area.signum() },
+		preserving: area > 0.0,
+		degenerate,
+	}
+}
+
+/// `v` moved into the plane `n` is the normal of and normalized, or zero if nothing is left
+fn project(n: Vector3<f32>, v: Vector3<f32>) -> Vector3<f32> {
+	let v = v - n * n.dot(v);
+	if v.magnitude2() > 0.0 { v.normalize() } else { v }
+}
+
+/// A unit vector perpendicular to `n` in the direction of `t`, or any one if `t` has none
+fn perpendicular(n: Vector3<f32>, t: Vector3<f32>) -> Vector3<f32> {
+	let t = t - n * n.dot(t);
+	if t.magnitude2() > 1e-12 {
+		return t.normalize();
+	}
+	let axis = if n.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+	let t = axis - n * n.dot(axis);
+	if t.magnitude2() > 0.0 { t.normalize() } else { Vector3::unit_x() }
+}
+
+fn value_key(v: &Vertex) -> [u32; 8] {
+	let mut key = [0; 8];
+	for (k, &f) in key.iter_mut().zip(v.a_Pos[..3].iter().chain(&v.a_Normal).chain(&v.a_TexCoord)) {
+		*k = weld::bits(f);
+	}
+	key
+}
+
+fn find(groups: &mut [usize], corner: usize) -> usize {
+	let mut root = corner;
+	while groups[root] != root {
+		root = groups[root];
+	}
+	let mut c = corner;
+	while groups[c] != root {
+		let next = groups[c];
+		groups[c] = root;
+		c = next;
+	}
+	root
+}
+
+fn union(groups: &mut [usize], a: usize, b: usize) {
+	let (a, b) = (find(groups, a), find(groups, b));
+	if a != b {
+		groups[b] = a;
+	}
+}
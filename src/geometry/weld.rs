@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use obj::MeshData;
+use td::Vertex;
+
+/// How much welding shrank a mesh
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WeldReport {
+	pub verts_before: usize,
+	pub verts_after: usize,
+}
+
+impl WeldReport {
+	pub fn removed(&self) -> usize {
+		self.verts_before - self.verts_after
+	}
+	
+	/// The fraction of vertices that were kept, from 0 to 1
+	pub fn ratio(&self) -> f32 {
+		if self.verts_before == 0 {
+			1.0
+		} else {
+			self.verts_after as f32 / self.verts_before as f32
+		}
+	}
+}
+
+/// Merges bit-identical vertices and rewrites the index buffer to refer to the merged ones
+pub fn weld(data: &mut MeshData) -> WeldReport {
+	let before = data.verts.len();
+	let mut lookup: HashMap<Key, u32> = HashMap::with_capacity(data.verts.len());
+	let mut verts = Vec::with_capacity(data.verts.len());
+	let mut remap = Vec::with_capacity(data.verts.len());
+	
+	for v in &data.verts {
+		let index = *lookup.entry(key(v)).or_insert_with(|| {
+			verts.push(*v);
+			(verts.len() - 1) as u32
+		});
+		remap.push(index);
+	}
+	
+	finish(data, verts, &remap, before)
+}
+
+/// Merges vertices whose attributes all differ by at most `epsilon`. Each merged vertex takes
+/// the attributes of the first vertex of its group in the original order. An `epsilon` of zero
+/// is the same as `weld`.
+pub fn weld_epsilon(data: &mut MeshData, epsilon: f32) -> WeldReport {
+	if epsilon <= 0.0 {
+		return weld(data);
+	}
+	
+	let before = data.verts.len();
+	// Positions are bucketed into cells as large as epsilon, so any match is in a neighbouring cell
+	let mut grid: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+	let mut verts: Vec<Vertex> = Vec::with_capacity(data.verts.len());
+	let mut remap = Vec::with_capacity(data.verts.len());
+	
+	for v in &data.verts {
+		let cell = [
+			(v.a_Pos[0] / epsilon).floor() as i64,
+			(v.a_Pos[1] / epsilon).floor() as i64,
+			(v.a_Pos[2] / epsilon).floor() as i64,
+		];
+		
+		let mut found = None;
+		'search: for dx in -1..2 {
+			for dy in -1..2 {
+				for dz in -1..2 {
+					if let Some(candidates) = grid.get(&[cell[0] + dx, cell[1] + dy, cell[2] + dz]) {
+						for &c in candidates {
+							if close(&verts[c as usize], v, epsilon) {
+								found = Some(c);
+								break 'search;
+							}
+						}
+					}
+				}
+			}
+		}
+		
+		let index = match found {
+			Some(index) => index,
+			None => {
+				verts.push(*v);
+				let index = (verts.len() - 1) as u32;
+				grid.entry(cell).or_insert_with(Vec::new).push(index);
+				index
+			},
+		};
+		remap.push(index);
+	}
+	
+	finish(data, verts, &remap, before)
+}
+
+fn finish(data: &mut MeshData, mut verts: Vec<Vertex>, remap: &[u32], before: usize) -> WeldReport {
+	for i in &mut data.indices {
+		*i = remap[*i as usize];
+	}
+	verts.shrink_to_fit();
+	data.verts = verts;
+	
+	WeldReport {
+		verts_before: before,
+		verts_after: data.verts.len(),
+	}
+}
+
+fn close(a: &Vertex, b: &Vertex, epsilon: f32) -> bool {
+	components(a).zip(components(b)).all(|(x, y)| (x - y).abs() <= epsilon)
+}
+
+fn components<'a>(v: &'a Vertex) -> impl Iterator<Item = f32> + 'a {
+	v.a_Pos.iter()
+		.chain(v.a_Color.iter())
+		.chain(v.a_Normal.iter())
+		.chain(v.a_TexCoord.iter())
+		.chain(v.a_Tangent.iter())
+		.cloned()
+}
+
+/// Every component of a vertex as raw bits
+pub(super) type Key = [u32; 17];
+
+pub(super) fn key(v: &Vertex) -> Key {
+	let mut key = [0; 17];
+	for (k, f) in key.iter_mut().zip(components(v)) {
+		*k = bits(f);
+	}
+	key
+}
+
+/// The bits of a float, with 0.0 and -0.0 treated as the same value
+pub(super) fn bits(f: f32) -> u32 {
+	if f == 0.0 { 0 } else { f.to_bits() }
+}
@@ -5,9 +5,20 @@ extern crate vulkano_shader_derive;
 extern crate winit;
 extern crate vulkano_win;
 extern crate cgmath;
+extern crate gltf;
 
+pub mod animation;
+pub mod control;
 pub mod obj;
+pub mod dynamic;
+pub mod format;
+pub mod geometry;
+pub mod shapes;
+pub mod skin;
+pub mod target;
 pub mod td;
+pub mod texture;
+pub mod view;
 pub mod math {
 	pub use cgmath::*;
 }
@@ -17,29 +28,41 @@ pub mod window {
 #[cfg(test)]
 mod tests;
 
-use td::{Vertex, Camera};
-use obj::Object;
+use td::{Vertex, MeshVertex, Camera, Light, Color};
+use obj::{Object, Mesh, Buffers, IndexBuffer, Topology, MAX_MORPH_TARGETS};
+use dynamic::Frame;
+use skin::{SkinnedVertex, MAX_JOINTS};
+use animation::Animations;
+use view::{View, Rect, Clear};
+use target::{RenderTarget, Refresh};
+use texture::Texture;
 
 use std::time::Instant;
 use std::sync::Arc;
 use std::mem;
-use std::collections::HashMap;
+use std::collections::{HashMap, BTreeMap};
+use std::any::TypeId;
 
 use vulkano::instance::{Instance, PhysicalDevice, Features};
 use vulkano::device::{Device, DeviceExtensions, Queue};
-use vulkano::buffer::CpuBufferPool;
-use vulkano::pipeline::GraphicsPipeline;
-use vulkano::pipeline::viewport::Viewport;
-use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, CpuBufferPool};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
+use vulkano::pipeline::viewport::{Viewport, Scissor};
+use vulkano::pipeline::depth_stencil::{DepthStencil, Compare};
+use vulkano::pipeline::blend::AttachmentBlend;
+use vulkano::descriptor::descriptor_set::{PersistentDescriptorSet, DescriptorSetsCollection};
 use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
 use vulkano::sync::{now, GpuFuture};
-use vulkano::framebuffer::{Framebuffer, Subpass};
+use vulkano::framebuffer::{Framebuffer, Subpass, RenderPassAbstract};
 use vulkano::format::Format;
 use vulkano::swapchain::{self, Surface, Swapchain, PresentMode, SurfaceTransform, SwapchainCreationError, AcquireError};
-use vulkano::image::SwapchainImage;
+use vulkano::image::{SwapchainImage, AttachmentImage};
 use vulkano_win::VkSurfaceBuild;
 use winit::{EventsLoop, WindowBuilder, Window, Event};
-use cgmath::{Matrix4, Deg};
+use cgmath::{Matrix4, SquareMatrix};
+
+/// Builds the pipeline for a vertex format, see `Renderer::add_vertex_format`
+pub type PipelineBuilder = Box<Fn(&Arc<Device>, Subpass<Arc<RenderPassAbstract + Send + Sync>>, Topology) -> Arc<GraphicsPipelineAbstract + Send + Sync>>;
 
 pub struct Renderer<A: App> {
 	app: A,
@@ -47,18 +70,39 @@ pub struct Renderer<A: App> {
 	events_loop: EventsLoop,
 	surface: Arc<Surface<Window>>,
 	pub targets: HashMap<String, Object>,
+	pub light: Light,
+	pub animations: Animations,
+	/// Drawn in order instead of the app's camera filling the window, when there are any. Their
+	/// cameras' aspect ratios are kept matching their rectangles.
+	pub views: Vec<View>,
+	/// Drawn before the window each frame, so their textures show this frame's picture. They're
+	/// drawn in order of their names, so a target showing another's texture should sort after it.
+	pub offscreen: BTreeMap<String, RenderTarget>,
+	formats: HashMap<TypeId, PipelineBuilder>,
 }
 
 pub struct RenderInternal {
 	pub(crate) device: Arc<Device>,
 	pub(crate) queue: Arc<Queue>,
 	pub(crate) swapchain: Arc<Swapchain<Window>>,
-	pub(crate) images: Vec<Arc<SwapchainImage<Window>>>
+	pub(crate) images: Vec<Arc<SwapchainImage<Window>>>,
+	/// Bound for objects whose material has no texture
+	pub(crate) white: Arc<Texture>,
+	/// Bound for objects whose material has no normal map
+	pub(crate) flat_normal: Arc<Texture>,
+	/// Whether lines can be drawn wider than one pixel
+	pub(crate) wide_lines: bool,
+	/// Whether depth goes from 1 at the near plane to 0 at the far plane
+	pub(crate) reversed_z: bool,
 }
 
 pub struct Context<'a> {
 	pub window: &'a Window,
 	pub targets: &'a mut HashMap<String, Object>,
+	pub light: &'a mut Light,
+	pub animations: &'a mut Animations,
+	pub views: &'a mut Vec<View>,
+	pub offscreen: &'a mut BTreeMap<String, RenderTarget>,
 	pub internal: &'a RenderInternal,
 }
 
@@ -76,22 +120,21 @@ impl<A: App> Renderer<A> {
 			[width, height]
 		};
 		
-		app.get_camera().proj = cgmath::PerspectiveFov {
-			fovy: Deg(45.0 as f32).into(),
-			aspect: dimensions[0] as f32 / dimensions[1] as f32,
-			near: 0.1,
-			far: 1000.0
-		};
-		
 		let instance_clone = Arc::clone(&instance);
 		let physical = PhysicalDevice::enumerate(&instance_clone).next()
 			.expect("No physical device available");
 		let queue_family = physical.queue_families()
 			.find(|&q| q.supports_graphics())
 			.expect("Couldn't find a graphical queue family");
+		let supported = physical.supported_features();
+		let features = Features {
+			wide_lines: supported.wide_lines,
+			large_points: supported.large_points,
+			..Features::none()
+		};
 		let (device, mut queues) = {
 			Device::new(physical,
-			            &Features::none(),
+			            &features,
 			            &DeviceExtensions {
 				            khr_swapchain: true,
 				            ..DeviceExtensions::none()
@@ -125,6 +168,9 @@ impl<A: App> Renderer<A> {
 				.expect("Failed to create swapchain")
 		};
 		
+		let white = Arc::new(Texture::upload(&device, &queue, 1, 1, vec![255; 4], true).unwrap());
+		let flat_normal = Arc::new(Texture::upload(&device, &queue, 1, 1, vec![128, 128, 255, 255], false).unwrap());
+		
 		let mut renderer = Renderer {
 			app,
 			internal: RenderInternal {
@@ -132,20 +178,65 @@ impl<A: App> Renderer<A> {
 				queue,
 				swapchain,
 				images,
+				white,
+				flat_normal,
+				wide_lines: features.wide_lines,
+				reversed_z: false,
 			},
 			events_loop,
 			surface: Arc::clone(&surface),
 			targets: HashMap::new(),
+			light: Light::default(),
+			animations: Animations::new(),
+			views: Vec::new(),
+			offscreen: BTreeMap::new(),
+			formats: HashMap::new(),
 		};
 		
+		renderer.app.resize(dimensions[0], dimensions[1], Context {
+			window: &renderer.surface.window(),
+			targets: &mut renderer.targets,
+			light: &mut renderer.light,
+			animations: &mut renderer.animations,
+			views: &mut renderer.views,
+			offscreen: &mut renderer.offscreen,
+			internal: &renderer.internal,
+		});
 		renderer.app.start(Context {
 			window: &renderer.surface.window(),
 			targets: &mut renderer.targets,
+			light: &mut renderer.light,
+			animations: &mut renderer.animations,
+			views: &mut renderer.views,
+			offscreen: &mut renderer.offscreen,
 			internal: &renderer.internal,
 		});
 		renderer
 	}
 	
+	/// Switches to reversed depth, where the near plane is at depth 1 and the far plane at 0, with a
+	/// floating point depth buffer instead of a 16-bit one. Together they spread depth precision
+	/// far more evenly, which avoids z-fighting in the distance, especially with a far plane at
+	/// infinity. It has to be set before `run`, and pipelines registered with
+	/// `add_vertex_format` then have to pass fragments with greater depth instead of lesser. Devices
+	/// that can't use a floating point depth buffer keep the 16-bit one, reversed.
+	pub fn set_reversed_z(&mut self, reversed: bool) {
+		self.internal.reversed_z = reversed;
+	}
+	
+	/// Registers the pipeline that meshes of vertex type `V` are drawn with. `build` gets the
+	/// subpass to render into and the topology of the mesh the first time one is drawn, and should
+	/// set up the builder like `Topology::primitive`, `Topology::is_strip` and `Topology::is_line`
+	/// say. Its shaders have to take the same descriptor sets as the built-in ones: the camera and
+	/// light in set 0, and the object's model, texture and normal map in set 1. Its viewport has to
+	/// be dynamic, and so should its scissor, which keeps views from drawing outside their
+	/// rectangle. Meshes whose vertex type has no pipeline are skipped.
+	pub fn add_vertex_format<V, F>(&mut self, build: F)
+		where V: MeshVertex, F: Fn(&Arc<Device>, Subpass<Arc<RenderPassAbstract + Send + Sync>>, Topology) -> Arc<GraphicsPipelineAbstract + Send + Sync> + 'static
+	{
+		self.formats.insert(TypeId::of::<V>(), Box::new(build));
+	}
+	
 	pub fn run(&mut self) {
 		let device = Arc::clone(&self.internal.device);
 		let queue = Arc::clone(&self.internal.queue);
@@ -156,7 +247,8 @@ impl<A: App> Renderer<A> {
 			[width, height]
 		};
 		
-		let render_pass = Arc::new(single_pass_renderpass!(device.clone(),
+		let depth_format = depth_format(&device, self.internal.reversed_z);
+		let render_pass: Arc<RenderPassAbstract + Send + Sync> = Arc::new(single_pass_renderpass!(device.clone(),
 			attachments: {
 				color: {
 					load: Clear,
@@ -167,7 +259,7 @@ impl<A: App> Renderer<A> {
 				depth: {
 					load: Clear,
 					store: DontCare,
-					format: Format::D16Unorm,
+					format: depth_format,
 					samples: 1,
 				}
 			},
@@ -177,25 +269,12 @@ impl<A: App> Renderer<A> {
 			}
 		).unwrap());
 		
-		let vs = vs::Shader::load(device.clone()).expect("Failed to create shader");
-		let fs = fs::Shader::load(device.clone()).expect("Failed to create shader");
-		
-		let pipeline = Arc::new(GraphicsPipeline::start()
-				.vertex_input_single_buffer::<Vertex>()
-				.vertex_shader(vs.main_entry_point(), ())
-				.viewports_dynamic_scissors_irrelevant(1)
-				.fragment_shader(fs.main_entry_point(), ())
-				.depth_stencil_simple_depth()
-				.render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
-				.build(device.clone())
-				.unwrap());
+		let mut recorder = Recorder::new(&device, render_pass.clone(), self.internal.reversed_z);
 		
 		let mut framebuffers: Option<Vec<Arc<Framebuffer<_, _>>>> = None;
 		let mut recreate_swapchain = false;
 		let mut previous_frame_end = Box::new(now(device.clone())) as Box<GpuFuture>;
-		let mut depth_buffer = vulkano::image::attachment::AttachmentImage::transient(device.clone(), dimensions, vulkano::format::D16Unorm).unwrap();
-		
-		let uniform_buffer = CpuBufferPool::<vs::ty::Data>::uniform_buffer(device.clone());
+		let mut depth_buffer = vulkano::image::attachment::AttachmentImage::transient(device.clone(), dimensions, depth_format).unwrap();
 		
 		while self.app.is_running() {
 			let start = Instant::now();
@@ -219,14 +298,17 @@ impl<A: App> Renderer<A> {
 				mem::replace(&mut swapchain, new_swapchain);
 				mem::replace(&mut self.internal.images, new_images);
 				
-				self.app.get_camera().proj = cgmath::PerspectiveFov {
-					fovy: Deg(45.0 as f32).into(),
-					aspect: dimensions[0] as f32 / dimensions[1] as f32,
-					near: 0.1,
-					far: 1000.0
-				};
+				self.app.resize(dimensions[0], dimensions[1], Context {
+					window: self.surface.window(),
+					targets: &mut self.targets,
+					light: &mut self.light,
+					animations: &mut self.animations,
+					views: &mut self.views,
+					offscreen: &mut self.offscreen,
+					internal: &self.internal,
+				});
 				
-				depth_buffer = vulkano::image::attachment::AttachmentImage::transient(device.clone(), dimensions, vulkano::format::D16Unorm).unwrap();
+				depth_buffer = vulkano::image::attachment::AttachmentImage::transient(device.clone(), dimensions, depth_format).unwrap();
 				
 				framebuffers = None;
 				recreate_swapchain = false;
@@ -251,47 +333,53 @@ impl<A: App> Renderer<A> {
 				Err(e) => panic!("{:?}", e)
 			};
 			
-			let uniform_buffer_sub = {
-				let uniform_data = vs::ty::Data {
-					proj: *Matrix4::from(self.app.get_camera().proj).as_ref(),
-					view: *self.app.get_camera().get_view().as_ref(),
-					viewPos: *self.app.get_camera().get_pos().as_ref(),
-				};
-				
-				uniform_buffer.next(uniform_data).unwrap()
-			};
+			let mut cmd_buffer = AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family()).unwrap();
 			
-			let set = Arc::new(PersistentDescriptorSet::start(pipeline.clone(), 0)
-				.add_buffer(uniform_buffer_sub).unwrap()
-				.build().unwrap());
+			let scene = Scene {
+				targets: &self.targets,
+				light: &self.light,
+				formats: &self.formats,
+				internal: &self.internal,
+			};
+			// Offscreen targets share the window's render pass, so the same pipelines draw into them
+			let mut readbacks = Vec::new();
+			for target in self.offscreen.values_mut() {
+				if target.refresh == Refresh::Paused {
+					continue;
+				}
+				let size = target.dimensions();
+				target.camera.proj.set_aspect(size[0] as f32 / size[1] as f32);
+				let framebuffer = match target.framebuffer(&render_pass, depth_format) {
+					Ok(framebuffer) => framebuffer,
+					// Without its images the target can't be drawn, so it stops trying
+					Err(()) => {
+						target.refresh = Refresh::Paused;
+						continue;
+					},
+				};
+				cmd_buffer = cmd_buffer
+						.begin_render_pass(framebuffer, false, vec![target.background.raw().into(), recorder.far_depth().into()])
+						.unwrap();
+				cmd_buffer = recorder.view(cmd_buffer, &scene, &target.camera, viewport(Rect::full(), size), None, target.layers, Some(&target.texture()));
+				cmd_buffer = cmd_buffer.end_render_pass().unwrap();
+				if target.read_back {
+					readbacks.extend(target.readback(&queue).ok());
+				}
+				if target.refresh == Refresh::Once {
+					target.refresh = Refresh::Paused;
+				}
+			}
 			
-			let mut cmd_buffer = AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family()).unwrap()
-					.begin_render_pass(framebuffers.as_ref().unwrap()[image_num].clone(), false, vec![[0.0, 0.0, 0.0, 1.0].into(), 1f32.into()])
+			cmd_buffer = cmd_buffer
+					.begin_render_pass(framebuffers.as_ref().unwrap()[image_num].clone(), false, vec![[0.0, 0.0, 0.0, 1.0].into(), recorder.far_depth().into()])
 					.unwrap();
-			
-			{
-				// Add a command for each object in the object
-				for (_, data) in &self.targets {
-					let dynamic_state = DynamicState {
-						viewports: Some(vec![Viewport {
-							origin: [0.0, 0.0],
-							dimensions: [dimensions[0] as f32, dimensions[1] as f32],
-							depth_range: 0.0..1.0,
-						}]),
-						..DynamicState::none()
-					};
-					
-					// Draw indexed call if the mesh has an index buffer
-					if let Some(ref ibuf) = data.mesh.indices {
-						cmd_buffer = cmd_buffer
-							.draw_indexed(pipeline.clone(), dynamic_state, Arc::clone(&data.mesh.verts), Arc::clone(&ibuf), set.clone(), ())
-							.unwrap();
-					} else {
-						// Draw the vertices as usual
-						cmd_buffer = cmd_buffer
-							.draw(pipeline.clone(), dynamic_state, Arc::clone(&data.mesh.verts), set.clone(), ())
-							.unwrap();
-					}
+			if self.views.is_empty() {
+				// The render pass already cleared the whole window
+				cmd_buffer = recorder.view(cmd_buffer, &scene, self.app.get_camera(), viewport(Rect::full(), dimensions), None, !0, None);
+			} else {
+				for view in &mut self.views {
+					view.camera.proj.set_aspect(view.rect.aspect(dimensions));
+					cmd_buffer = recorder.view(cmd_buffer, &scene, &view.camera, viewport(view.rect, dimensions), Some(view.clear), view.layers, None);
 				}
 			}
 			
@@ -302,8 +390,13 @@ impl<A: App> Renderer<A> {
 					.build()
 					.unwrap();
 			
-			let future = previous_frame_end.join(acquire_future)
-					.then_execute(queue.clone(), cmd_buffer).unwrap()
+			let mut frame = Box::new(previous_frame_end.join(acquire_future)
+					.then_execute(queue.clone(), cmd_buffer).unwrap()) as Box<GpuFuture>;
+			// Pictures are copied out once the frame has drawn them
+			for readback in readbacks {
+				frame = Box::new(frame.then_execute(queue.clone(), readback).unwrap());
+			}
+			let future = frame
 					.then_swapchain_present(queue.clone(), swapchain.clone(), image_num)
 					.then_signal_fence_and_flush().unwrap();
 			
@@ -313,12 +406,20 @@ impl<A: App> Renderer<A> {
 			let events_loop = &mut self.events_loop;
 			let surface = &self.surface;
 			let targets = &mut self.targets;
+			let light = &mut self.light;
+			let animations = &mut self.animations;
+			let views = &mut self.views;
+			let offscreen = &mut self.offscreen;
 			let internal = &self.internal;
 			
 			events_loop.poll_events(|event| {
 				app.handle_event(event.clone(), Context {
 					window: surface.window(),
 					targets,
+					light,
+					animations,
+					views,
+					offscreen,
 					internal,
 				});
 				
@@ -336,21 +437,376 @@ impl<A: App> Renderer<A> {
 			
 			let elapsed = start.elapsed();
 			let ms = (elapsed.as_secs() as f64 * 1000.0f64 + elapsed.subsec_nanos() as f64 / 1_000_000.0f64) as f32;
+			animations.advance(ms);
+			animations.apply(targets, app.get_camera(), views, light);
 			app.update(ms, Context {
 				window: surface.window(),
 				targets,
+				light,
+				animations,
+				views,
+				offscreen,
 				internal,
 			});
 		}
 	}
 }
 
+/// The shaders for the vertex formats that are drawn without `Renderer::add_vertex_format`, and
+/// for clearing views
+struct Shaders {
+	vs: vs::Shader,
+	skinned_vs: skinned_vs::Shader,
+	morph_vs: morph_vs::Shader,
+	fs: fs::Shader,
+	clear_vs: clear_vs::Shader,
+	clear_fs: clear_fs::Shader,
+}
+
+/// What a view draws, borrowed from the renderer for one frame
+struct Scene<'a> {
+	targets: &'a HashMap<String, Object>,
+	light: &'a Light,
+	formats: &'a HashMap<TypeId, PipelineBuilder>,
+	internal: &'a RenderInternal,
+}
+
+/// Everything `run` keeps between frames to record the draw calls for views of the scene
+struct Recorder {
+	device: Arc<Device>,
+	render_pass: Arc<RenderPassAbstract + Send + Sync>,
+	shaders: Shaders,
+	reversed_z: bool,
+	/// Each vertex type and topology is drawn with its own pipeline, built when first needed
+	pipelines: HashMap<(TypeId, Topology, bool), Arc<GraphicsPipelineAbstract + Send + Sync>>,
+	/// Fill a view's rectangle, by whether they clear color and depth
+	clear_pipelines: HashMap<(bool, bool), Arc<GraphicsPipelineAbstract + Send + Sync>>,
+	/// A triangle covering the whole viewport
+	clear_verts: Arc<CpuAccessibleBuffer<[Vertex]>>,
+	uniform_buffer: CpuBufferPool<vs::ty::Data>,
+	model_buffer: CpuBufferPool<vs::ty::Model>,
+	joint_buffer: CpuBufferPool<skinned_vs::ty::Joints>,
+	morph_buffer: CpuBufferPool<morph_vs::ty::Morph>,
+}
+
+impl Recorder {
+	fn new(device: &Arc<Device>, render_pass: Arc<RenderPassAbstract + Send + Sync>, reversed_z: bool) -> Self {
+		let shaders = Shaders {
+			vs: vs::Shader::load(device.clone()).expect("Failed to create shader"),
+			skinned_vs: skinned_vs::Shader::load(device.clone()).expect("Failed to create shader"),
+			morph_vs: morph_vs::Shader::load(device.clone()).expect("Failed to create shader"),
+			fs: fs::Shader::load(device.clone()).expect("Failed to create shader"),
+			clear_vs: clear_vs::Shader::load(device.clone()).expect("Failed to create shader"),
+			clear_fs: clear_fs::Shader::load(device.clone()).expect("Failed to create shader"),
+		};
+		
+		// The pipeline for plain triangles is always there, to make the camera's descriptor set with
+		let mut pipelines = HashMap::new();
+		let subpass = Subpass::from(render_pass.clone(), 0).unwrap();
+		pipelines.insert((TypeId::of::<Vertex>(), Topology::TriangleList, false), builtin_pipeline(device, subpass, &shaders, TypeId::of::<Vertex>(), Topology::TriangleList, false, reversed_z).unwrap());
+		
+		let corners = [Vertex::new(-1.0, -1.0, 0.0, 1.0, Color::white()), Vertex::new(3.0, -1.0, 0.0, 1.0, Color::white()), Vertex::new(-1.0, 3.0, 0.0, 1.0, Color::white())];
+		let clear_verts = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::vertex_buffer(), corners.iter().cloned()).unwrap();
+		
+		Recorder {
+			device: device.clone(),
+			render_pass,
+			shaders,
+			reversed_z,
+			pipelines,
+			clear_pipelines: HashMap::new(),
+			clear_verts,
+			uniform_buffer: CpuBufferPool::uniform_buffer(device.clone()),
+			model_buffer: CpuBufferPool::uniform_buffer(device.clone()),
+			joint_buffer: CpuBufferPool::uniform_buffer(device.clone()),
+			morph_buffer: CpuBufferPool::uniform_buffer(device.clone()),
+		}
+	}
+	
+	/// The depth buffer value at the far plane, which it's cleared to
+	fn far_depth(&self) -> f32 {
+		if self.reversed_z { 0.0 } else { 1.0 }
+	}
+	
+	/// Records the draw calls for the objects on any of `layers` as seen by `camera`. Objects using
+	/// `drawing_into` are left out, since a render target can't be read while it's drawn to.
+	fn view(&mut self, mut cmd_buffer: AutoCommandBufferBuilder, scene: &Scene, camera: &Camera, viewport: Viewport, clear: Option<Clear>, layers: u32, drawing_into: Option<&Arc<Texture>>) -> AutoCommandBufferBuilder {
+		if let Some(clear) = clear {
+			cmd_buffer = self.clear(cmd_buffer, viewport.clone(), clear);
+		}
+		
+		let uniform_buffer_sub = {
+			let light = scene.light;
+			let uniform_data = vs::ty::Data {
+				proj: *(depth_correction(self.reversed_z) * camera.proj.matrix()).as_ref(),
+				view: *camera.get_view().as_ref(),
+				lightPos: *light.position.extend(1.0).as_ref(),
+				lightColor: light.color.raw(),
+				viewPos: *camera.get_pos().as_ref(),
+				ambient: light.ambient,
+			};
+			
+			self.uniform_buffer.next(uniform_data).unwrap()
+		};
+		
+		let set = Arc::new(PersistentDescriptorSet::start(self.pipelines[&(TypeId::of::<Vertex>(), Topology::TriangleList, false)].clone(), 0)
+			.add_buffer(uniform_buffer_sub).unwrap()
+			.build().unwrap());
+		
+		// Add a command for each object in the object
+		for (_, data) in scene.targets {
+			if data.layers & layers == 0 {
+				continue;
+			}
+			if let Some(into) = drawing_into {
+				let uses = |texture: &Option<Arc<Texture>>| texture.as_ref().map_or(false, |t| Arc::ptr_eq(t, into));
+				if uses(&data.material.texture) || uses(&data.material.normal_map) {
+					continue;
+				}
+			}
+			let mesh = data.mesh_for(camera);
+			let key = (mesh.format, mesh.topology, mesh.morphs.is_some());
+			if !self.pipelines.contains_key(&key) {
+				let render_pass = self.render_pass.clone();
+				let subpass = || Subpass::from(render_pass.clone(), 0).unwrap();
+				let built = builtin_pipeline(&self.device, subpass(), &self.shaders, mesh.format, mesh.topology, key.2, self.reversed_z)
+					.or_else(|| scene.formats.get(&mesh.format).map(|build| build(&self.device, subpass(), mesh.topology)));
+				match built {
+					Some(built) => self.pipelines.insert(key, built),
+					None => continue,
+				};
+			}
+			let pipeline = &self.pipelines[&key];
+			
+			let line_width = if scene.internal.wide_lines { data.material.line_width } else { 1.0 };
+			let dynamic_state = DynamicState {
+				line_width: if mesh.topology.is_line() { Some(line_width) } else { None },
+				viewports: Some(vec![viewport.clone()]),
+				scissors: if pipeline.has_dynamic_scissors() { Some(vec![scissor(&viewport)]) } else { None },
+				..DynamicState::none()
+			};
+			
+			let model_buffer_sub = self.model_buffer.next(vs::ty::Model {
+				model: *data.transform.as_ref(),
+				color: data.material.color.raw(),
+				pointSize: data.material.point_size,
+			}).unwrap();
+			let texture = data.material.texture.as_ref().unwrap_or(&scene.internal.white);
+			let normal_map = data.material.normal_map.as_ref().unwrap_or(&scene.internal.flat_normal);
+			
+			let object_set = PersistentDescriptorSet::start(pipeline.clone(), 1)
+				.add_buffer(model_buffer_sub).unwrap()
+				.add_sampled_image(texture.image.clone(), texture.sampler.clone()).unwrap()
+				.add_sampled_image(normal_map.image.clone(), normal_map.sampler.clone()).unwrap();
+			
+			if mesh.format == TypeId::of::<SkinnedVertex>() {
+				// Without a skin the mesh stays in its bind pose
+				let mut joints = [[[0.0; 4]; 4]; MAX_JOINTS];
+				let matrices = data.skin.as_ref().map(|skin| skin.matrices()).unwrap_or_default();
+				for (i, joint) in joints.iter_mut().enumerate() {
+					*joint = *matrices.get(i).cloned().unwrap_or(Matrix4::identity()).as_ref();
+				}
+				let joint_buffer_sub = self.joint_buffer.next(skinned_vs::ty::Joints { joints }).unwrap();
+				let object_set = Arc::new(object_set.add_buffer(joint_buffer_sub).unwrap().build().unwrap());
+				cmd_buffer = draw_mesh(cmd_buffer, pipeline, dynamic_state, mesh, (set.clone(), object_set));
+			} else if let Some(ref morphs) = mesh.morphs {
+				let mut weights = [[0.0; 4]; MAX_MORPH_TARGETS / 4];
+				for (i, &weight) in data.morph_weights.iter().take(morphs.names.len()).enumerate() {
+					weights[i / 4][i % 4] = weight;
+				}
+				let morph_buffer_sub = self.morph_buffer.next(morph_vs::ty::Morph {
+					weights,
+					vertexCount: mesh.vertex_count() as u32,
+					targetCount: morphs.names.len() as u32,
+				}).unwrap();
+				let object_set = Arc::new(object_set
+					.add_buffer(morph_buffer_sub).unwrap()
+					.add_buffer(morphs.deltas.clone()).unwrap()
+					.build().unwrap());
+				cmd_buffer = draw_mesh(cmd_buffer, pipeline, dynamic_state, mesh, (set.clone(), object_set));
+			} else {
+				let object_set = Arc::new(object_set.build().unwrap());
+				cmd_buffer = draw_mesh(cmd_buffer, pipeline, dynamic_state, mesh, (set.clone(), object_set));
+			}
+		}
+		cmd_buffer
+	}
+	
+	/// Wipes a viewport by drawing over all of it at the far plane
+	fn clear(&mut self, cmd_buffer: AutoCommandBufferBuilder, viewport: Viewport, clear: Clear) -> AutoCommandBufferBuilder {
+		let key = (clear.color.is_some(), clear.depth);
+		if key == (false, false) {
+			return cmd_buffer;
+		}
+		if !self.clear_pipelines.contains_key(&key) {
+			let subpass = Subpass::from(self.render_pass.clone(), 0).unwrap();
+			let pipeline = clear_pipeline(&self.device, subpass, &self.shaders, key.0, key.1);
+			self.clear_pipelines.insert(key, pipeline);
+		}
+		
+		let values = clear_vs::ty::ClearValues {
+			color: clear.color.unwrap_or(Color::black()).raw(),
+			depth: self.far_depth(),
+		};
+		let dynamic_state = DynamicState {
+			scissors: Some(vec![scissor(&viewport)]),
+			viewports: Some(vec![viewport]),
+			..DynamicState::none()
+		};
+		cmd_buffer
+			.draw(self.clear_pipelines[&key].clone(), dynamic_state, vec![self.clear_verts.clone()], (), values)
+			.unwrap()
+	}
+}
+
+/// The viewport covering `rect` of a target `size` pixels big
+fn viewport(rect: Rect, size: [u32; 2]) -> Viewport {
+	let (origin, dimensions) = rect.pixels(size);
+	Viewport {
+		origin,
+		dimensions,
+		depth_range: 0.0..1.0,
+	}
+}
+
+/// The scissor keeping draws inside `viewport`, rounded to whole pixels so that views sharing an
+/// edge neither overlap nor leave a gap
+fn scissor(viewport: &Viewport) -> Scissor {
+	let start = [viewport.origin[0].round(), viewport.origin[1].round()];
+	let end = [(viewport.origin[0] + viewport.dimensions[0]).round(), (viewport.origin[1] + viewport.dimensions[1]).round()];
+	Scissor {
+		origin: [start[0] as i32, start[1] as i32],
+		dimensions: [(end[0] - start[0]) as u32, (end[1] - start[1]) as u32],
+	}
+}
+
+/// Builds the pipeline for one of the built-in vertex formats, or `None` for any other format.
+/// `morph` picks the variant for meshes with morph targets.
+fn builtin_pipeline(device: &Arc<Device>, subpass: Subpass<Arc<RenderPassAbstract + Send + Sync>>, shaders: &Shaders, format: TypeId, topology: Topology, morph: bool, reversed_z: bool) -> Option<Arc<GraphicsPipelineAbstract + Send + Sync>> {
+	let depth = DepthStencil {
+		depth_compare: if reversed_z { Compare::Greater } else { Compare::Less },
+		..DepthStencil::simple_depth_test()
+	};
+	macro_rules! build {
+		($vertex:ty, $vs:expr) => {{
+			let builder = GraphicsPipeline::start()
+				.vertex_input_single_buffer::<$vertex>()
+				.vertex_shader($vs.main_entry_point(), ())
+				.primitive_topology(topology.primitive())
+				.primitive_restart(topology.is_strip())
+				.viewports_scissors_dynamic(1)
+				.fragment_shader(shaders.fs.main_entry_point(), ())
+				.depth_stencil(depth.clone())
+				.render_pass(subpass);
+			let builder = if topology.is_line() { builder.line_width_dynamic() } else { builder };
+			Arc::new(builder.build(device.clone()).unwrap()) as Arc<GraphicsPipelineAbstract + Send + Sync>
+		}};
+	}
+	
+	if format == TypeId::of::<Vertex>() && morph {
+		Some(build!(Vertex, shaders.morph_vs))
+	} else if format == TypeId::of::<Vertex>() {
+		Some(build!(Vertex, shaders.vs))
+	} else if format == TypeId::of::<SkinnedVertex>() {
+		Some(build!(SkinnedVertex, shaders.skinned_vs))
+	} else {
+		None
+	}
+}
+
+/// Builds the pipeline that fills a viewport, writing only color or only depth if one of them
+/// isn't cleared
+fn clear_pipeline(device: &Arc<Device>, subpass: Subpass<Arc<RenderPassAbstract + Send + Sync>>, shaders: &Shaders, color: bool, depth: bool) -> Arc<GraphicsPipelineAbstract + Send + Sync> {
+	let blend = AttachmentBlend {
+		mask_red: color,
+		mask_green: color,
+		mask_blue: color,
+		mask_alpha: color,
+		..AttachmentBlend::pass_through()
+	};
+	Arc::new(GraphicsPipeline::start()
+		.vertex_input_single_buffer::<Vertex>()
+		.vertex_shader(shaders.clear_vs.main_entry_point(), ())
+		.triangle_list()
+		.viewports_scissors_dynamic(1)
+		.fragment_shader(shaders.clear_fs.main_entry_point(), ())
+		.depth_stencil(DepthStencil {
+			depth_compare: Compare::Always,
+			depth_write: depth,
+			..DepthStencil::simple_depth_test()
+		})
+		.blend_collective(blend)
+		.render_pass(subpass)
+		.build(device.clone())
+		.unwrap())
+}
+
+/// The format of the depth buffer. Reversed depth only gains precision with floats, which are
+/// densest near zero, so it gets them wherever the device can use them as a depth attachment.
+fn depth_format(device: &Arc<Device>, reversed_z: bool) -> Format {
+	// Making an image fails if its format doesn't support the usage with optimal tiling
+	let floats = || AttachmentImage::transient(device.clone(), [1, 1], Format::D32Sfloat).is_ok();
+	if reversed_z && floats() { Format::D32Sfloat } else { Format::D16Unorm }
+}
+
+/// Maps the depth the camera's projection gives, where the near plane is at -1 and the far plane
+/// at 1, to the 0 to 1 range of the depth buffer, reversed if it's enabled
+fn depth_correction(reversed_z: bool) -> Matrix4<f32> {
+	let scale = if reversed_z { -0.5 } else { 0.5 };
+	Matrix4::new(
+		1.0, 0.0, 0.0, 0.0,
+		0.0, 1.0, 0.0, 0.0,
+		0.0, 0.0, scale, 0.0,
+		0.0, 0.0, 0.5, 1.0,
+	)
+}
+
+/// Records the draw call for a mesh, or nothing for an empty dynamic mesh
+fn draw_mesh<S>(cmd_buffer: AutoCommandBufferBuilder, pipeline: &Arc<GraphicsPipelineAbstract + Send + Sync>, dynamic_state: DynamicState, mesh: &Mesh, sets: S) -> AutoCommandBufferBuilder
+	where S: DescriptorSetsCollection
+{
+	match mesh.buffers {
+		Buffers::Immutable { ref verts, ref indices, .. } => match *indices {
+			// Draw indexed call if the mesh has an index buffer
+			Some(IndexBuffer::U16(ref ibuf)) => cmd_buffer
+				.draw_indexed(pipeline.clone(), dynamic_state, vec![verts.clone()], ibuf.clone(), sets, ())
+				.unwrap(),
+			Some(IndexBuffer::U32(ref ibuf)) => cmd_buffer
+				.draw_indexed(pipeline.clone(), dynamic_state, vec![verts.clone()], ibuf.clone(), sets, ())
+				.unwrap(),
+			// Draw the vertices as usual
+			None => cmd_buffer
+				.draw(pipeline.clone(), dynamic_state, vec![verts.clone()], sets, ())
+				.unwrap(),
+		},
+		Buffers::Dynamic(ref dynamic) => match dynamic.frame() {
+			Some(Frame { verts, indices: Some(ibuf) }) => cmd_buffer
+				.draw_indexed(pipeline.clone(), dynamic_state, vec![verts], ibuf, sets, ())
+				.unwrap(),
+			Some(Frame { verts, indices: None }) => cmd_buffer
+				.draw(pipeline.clone(), dynamic_state, vec![verts], sets, ())
+				.unwrap(),
+			// Nothing is drawn while a dynamic mesh is empty
+			None => cmd_buffer,
+		},
+	}
+}
+
 pub trait App {
 	fn get_camera(&mut self) -> &mut Camera;
 	fn handle_event(&mut self, event: Event, context: Context);
 	fn update(&mut self, ms: f32, context: Context);
 	fn is_running(&self) -> bool;
 	fn start(&mut self, _context: Context) { }
+	
+	/// Called with the window's size in pixels when the renderer is created and whenever the
+	/// window is resized. By default the camera's projection is fitted to the new aspect ratio,
+	/// leaving its field of view and clip planes alone.
+	fn resize(&mut self, width: u32, height: u32, _context: Context) {
+		if width > 0 && height > 0 {
+			self.get_camera().proj.set_aspect(width as f32 / height as f32);
+		}
+	}
 }
 
 mod vs {
@@ -361,24 +817,172 @@ mod vs {
 layout(location = 0) in vec4 a_Pos;
 layout(location = 1) in vec4 a_Color;
 layout(location = 2) in vec3 a_Normal;
+layout(location = 3) in vec2 a_TexCoord;
+layout(location = 4) in vec4 a_Tangent;
+
+layout(location = 0) out vec4 v_Color;
+layout(location = 1) out vec3 v_Normal;
+layout(location = 2) out vec3 v_Pos;
+layout(location = 3) out vec3 viewPos;
+layout(location = 4) out vec2 v_TexCoord;
+layout(location = 5) out vec4 v_Tangent;
+
+layout(set = 0, binding = 0) uniform Data {
+	mat4 proj;
+	mat4 view;
+	vec4 lightPos;
+	vec4 lightColor;
+	vec3 viewPos;
+	float ambient;
+} uniforms;
+
+layout(set = 1, binding = 0) uniform Model {
+	mat4 model;
+	vec4 color;
+	float pointSize;
+} object;
+
+void main() {
+	vec4 world = object.model * vec4(a_Pos.xyz, 1.0);
+	v_Color = a_Color * object.color;
+    gl_Position = uniforms.proj * uniforms.view * world;
+	gl_PointSize = object.pointSize;
+	v_Pos = world.xyz;
+	v_Normal = mat3(transpose(inverse(object.model))) * a_Normal;
+	v_Tangent = vec4(mat3(object.model) * a_Tangent.xyz, a_Tangent.w);
+	viewPos = uniforms.viewPos;
+	v_TexCoord = a_TexCoord;
+}
+"]
+	#[allow(dead_code)]
+	struct Dummy;
+}
+
+/// `vs` with the mesh's morph targets blended in by the object's weights, read by vertex index
+mod morph_vs {
+	#[derive(VulkanoShader)]
+	#[ty = "vertex"]
+	#[src = "#version 450 core
+
+layout(location = 0) in vec4 a_Pos;
+layout(location = 1) in vec4 a_Color;
+layout(location = 2) in vec3 a_Normal;
+layout(location = 3) in vec2 a_TexCoord;
+layout(location = 4) in vec4 a_Tangent;
+
+layout(location = 0) out vec4 v_Color;
+layout(location = 1) out vec3 v_Normal;
+layout(location = 2) out vec3 v_Pos;
+layout(location = 3) out vec3 viewPos;
+layout(location = 4) out vec2 v_TexCoord;
+layout(location = 5) out vec4 v_Tangent;
+
+layout(set = 0, binding = 0) uniform Data {
+	mat4 proj;
+	mat4 view;
+	vec4 lightPos;
+	vec4 lightColor;
+	vec3 viewPos;
+	float ambient;
+} uniforms;
+
+layout(set = 1, binding = 0) uniform Model {
+	mat4 model;
+	vec4 color;
+	float pointSize;
+} object;
+
+layout(set = 1, binding = 3) uniform Morph {
+	vec4 weights[16];
+	uint vertexCount;
+	uint targetCount;
+} morph;
+
+layout(set = 1, binding = 4) readonly buffer Deltas {
+	vec4 deltas[];
+} targets;
+
+void main() {
+	vec3 pos = a_Pos.xyz;
+	vec3 normal = a_Normal;
+	for (uint t = 0; t < morph.targetCount; t++) {
+		float weight = morph.weights[t / 4][t % 4];
+		if (weight != 0.0) {
+			uint base = (t * morph.vertexCount + uint(gl_VertexIndex)) * 2;
+			pos += weight * targets.deltas[base].xyz;
+			normal += weight * targets.deltas[base + 1].xyz;
+		}
+	}
+	
+	vec4 world = object.model * vec4(pos, 1.0);
+	v_Color = a_Color * object.color;
+	gl_Position = uniforms.proj * uniforms.view * world;
+	gl_PointSize = object.pointSize;
+	v_Pos = world.xyz;
+	v_Normal = mat3(transpose(inverse(object.model))) * normal;
+	v_Tangent = vec4(mat3(object.model) * a_Tangent.xyz, a_Tangent.w);
+	viewPos = uniforms.viewPos;
+	v_TexCoord = a_TexCoord;
+}
+"]
+	#[allow(dead_code)]
+	struct Dummy;
+}
+
+/// `vs` with each vertex moved by a weighted blend of up to four joint matrices
+mod skinned_vs {
+	#[derive(VulkanoShader)]
+	#[ty = "vertex"]
+	#[src = "#version 450 core
+
+layout(location = 0) in vec4 a_Pos;
+layout(location = 1) in vec4 a_Color;
+layout(location = 2) in vec3 a_Normal;
+layout(location = 3) in vec2 a_TexCoord;
+layout(location = 4) in vec4 a_Tangent;
+layout(location = 5) in uvec4 a_Joints;
+layout(location = 6) in vec4 a_Weights;
 
 layout(location = 0) out vec4 v_Color;
 layout(location = 1) out vec3 v_Normal;
 layout(location = 2) out vec3 v_Pos;
 layout(location = 3) out vec3 viewPos;
+layout(location = 4) out vec2 v_TexCoord;
+layout(location = 5) out vec4 v_Tangent;
 
 layout(set = 0, binding = 0) uniform Data {
 	mat4 proj;
 	mat4 view;
+	vec4 lightPos;
+	vec4 lightColor;
 	vec3 viewPos;
+	float ambient;
 } uniforms;
 
+layout(set = 1, binding = 0) uniform Model {
+	mat4 model;
+	vec4 color;
+	float pointSize;
+} object;
+
+layout(set = 1, binding = 3) uniform Joints {
+	mat4 joints[64];
+} skin;
+
 void main() {
-	v_Color = a_Color;
-    gl_Position = uniforms.proj * uniforms.view * vec4(a_Pos.xyz, 1.0);
-	v_Pos = a_Pos.xyz;
-	v_Normal = a_Normal;
+	mat4 skinned = object.model * (a_Weights.x * skin.joints[a_Joints.x]
+		+ a_Weights.y * skin.joints[a_Joints.y]
+		+ a_Weights.z * skin.joints[a_Joints.z]
+		+ a_Weights.w * skin.joints[a_Joints.w]);
+	vec4 world = skinned * vec4(a_Pos.xyz, 1.0);
+	v_Color = a_Color * object.color;
+	gl_Position = uniforms.proj * uniforms.view * world;
+	gl_PointSize = object.pointSize;
+	v_Pos = world.xyz;
+	v_Normal = mat3(transpose(inverse(skinned))) * a_Normal;
+	v_Tangent = vec4(mat3(skinned) * a_Tangent.xyz, a_Tangent.w);
 	viewPos = uniforms.viewPos;
+	v_TexCoord = a_TexCoord;
 }
 "]
 	#[allow(dead_code)]
@@ -394,31 +998,99 @@ layout(location = 0) in vec4 v_Color;
 layout(location = 1) in vec3 v_Normal;
 layout(location = 2) in vec3 v_Pos;
 layout(location = 3) in vec3 viewPos;
+layout(location = 4) in vec2 v_TexCoord;
+layout(location = 5) in vec4 v_Tangent;
 
 layout(location = 0) out vec4 f_Color;
 
+layout(set = 0, binding = 0) uniform Data {
+	mat4 proj;
+	mat4 view;
+	vec4 lightPos;
+	vec4 lightColor;
+	vec3 viewPos;
+	float ambient;
+} uniforms;
+
+layout(set = 1, binding = 1) uniform sampler2D tex;
+layout(set = 1, binding = 2) uniform sampler2D normalMap;
+
 void main() {
-	float ambientStrength = 0.1;
-	vec3 ambient = ambientStrength * vec3(1.0, 1.0, 1.0);
+	vec4 base = v_Color * texture(tex, v_TexCoord);
+	// Points and lines usually come without normals, and there's no direction to light them from
+	if (dot(v_Normal, v_Normal) == 0.0) {
+		f_Color = base;
+		return;
+	}
+	
+	vec3 lightColor = uniforms.lightColor.rgb;
+	vec3 lightPos = uniforms.lightPos.xyz;
+	vec3 ambient = uniforms.ambient * lightColor;
 	
-	vec3 lightColor = vec3(1.0, 1.0, 1.0);
-	vec3 lightPos = vec3(4.0, 3.0, 2.0);
 	vec3 norm = normalize(v_Normal);
+	// Meshes without tangents have no tangent space to map normals into
+	if (dot(v_Tangent.xyz, v_Tangent.xyz) > 0.0) {
+		vec3 tangent = normalize(v_Tangent.xyz - norm * dot(norm, v_Tangent.xyz));
+		vec3 bitangent = cross(norm, tangent) * v_Tangent.w;
+		vec3 mapped = texture(normalMap, v_TexCoord).xyz * 2.0 - 1.0;
+		norm = normalize(mat3(tangent, bitangent, norm) * mapped);
+	}
 	vec3 lightDir = normalize(lightPos - v_Pos);
-
+	
 	float diff = max(dot(norm, lightDir), 0.0);
 	vec3 diffuse = diff * lightColor;
-
+	
 	float specularStrength = 0.5;
 	vec3 viewDir = normalize(viewPos - v_Pos);
 	vec3 reflectDir = reflect(-lightDir, norm);
 	float spec = pow(max(dot(viewDir, reflectDir), 0.0), 32);
 	vec3 specular = specularStrength * spec * lightColor;
-
-	vec4 result = vec4((ambient + diffuse + specular) * v_Color.xyz, v_Color.w);
+	
+	vec4 result = vec4((ambient + diffuse + specular) * base.xyz, base.w);
     f_Color = result;
 }
 "]
 	#[allow(dead_code)]
 	struct Dummy;
 }
+
+/// Covers the viewport with one triangle at a fixed depth, passing the clear color on
+mod clear_vs {
+	#[derive(VulkanoShader)]
+	#[ty = "vertex"]
+	#[src = "#version 450 core
+
+layout(location = 0) in vec4 a_Pos;
+
+layout(location = 0) out vec4 v_Color;
+
+layout(push_constant) uniform ClearValues {
+	vec4 color;
+	float depth;
+} clear;
+
+void main() {
+	v_Color = clear.color;
+	gl_Position = vec4(a_Pos.xy, clear.depth, 1.0);
+}
+"]
+	#[allow(dead_code)]
+	struct Dummy;
+}
+
+mod clear_fs {
+	#[derive(VulkanoShader)]
+	#[ty = "fragment"]
+	#[src = "#version 450 core
+
+layout(location = 0) in vec4 v_Color;
+
+layout(location = 0) out vec4 f_Color;
+
+void main() {
+	f_Color = v_Color;
+}
+"]
+	#[allow(dead_code)]
+	struct Dummy;
+}
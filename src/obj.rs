@@ -1,68 +1,673 @@
 use std::sync::Arc;
+use std::any::{Any, TypeId};
 use std::fmt;
+use std::borrow::Cow;
 
-use vulkano::buffer::{ImmutableBuffer, BufferUsage};
+use vulkano::buffer::{ImmutableBuffer, CpuAccessibleBuffer, BufferUsage, BufferAccess, TypedBufferAccess};
+use vulkano::pipeline::input_assembly::{Index, PrimitiveTopology};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBuffer};
+use vulkano::sync::GpuFuture;
+use cgmath::{Matrix4, SquareMatrix, InnerSpace, Zero};
 
 use RenderInternal;
 use td::*;
+use texture::Texture;
+use geometry::{self, WeldReport};
+use dynamic::{DynamicMesh, DynamicBuffers};
+use skin::Skin;
 
 pub struct Mesh {
-	pub(crate) verts: Arc<ImmutableBuffer<[Vertex]>>,
-	pub(crate) indices: Option<Arc<ImmutableBuffer<[u32]>>>,
+	pub(crate) buffers: Buffers,
+	/// The vertex type, which picks the pipeline the mesh is drawn with
+	pub(crate) format: TypeId,
+	pub(crate) topology: Topology,
+	pub(crate) morphs: Option<Morphs>,
+	bounds: BoundingSphere,
+	/// See `Mesh::joint_count`
+	joints: usize,
+	/// A CPU-side copy of the buffers, see `MeshOptions::keep_data` and `Mesh::read_back`. The
+	/// indices are left empty for meshes without an index buffer.
+	data: Option<MeshData>,
+}
+
+/// Where a mesh's vertices and indices live on the GPU
+pub(crate) enum Buffers {
+	Immutable {
+		verts: Arc<BufferAccess + Send + Sync>,
+		/// The same buffer as `verts` with its vertex type intact, for reading it back
+		typed_verts: Arc<Any + Send + Sync>,
+		vertex_count: usize,
+		indices: Option<IndexBuffer>,
+	},
+	Dynamic(Box<DynamicBuffers>),
+}
+
+/// The most morph targets a mesh can have
+pub const MAX_MORPH_TARGETS: usize = 64;
+
+/// Offsets from a mesh's vertices that can be blended in by weight, see `Object::morph_weights`.
+/// The offsets follow the order of the vertices as they're uploaded, so targets for data that is
+/// optimized on the way should be passed to `Mesh::from_data_with_targets`.
+#[derive(Clone, Debug)]
+pub struct MorphTarget {
+	pub name: String,
+	/// One offset per vertex
+	pub positions: Vec<Vec3>,
+	/// One offset per vertex, or none to leave the normals alone
+	pub normals: Option<Vec<Vec3>>,
+}
+
+impl MorphTarget {
+	/// Reorders the offsets to follow vertices that were moved or split, where `sources[i]` is
+	/// the index vertex `i` had before
+	pub(crate) fn remap(&mut self, sources: &[u32]) {
+		self.positions = sources.iter().map(|&i| self.positions[i as usize]).collect();
+		if let Some(ref mut normals) = self.normals {
+			*normals = sources.iter().map(|&i| normals[i as usize]).collect();
+		}
+	}
+	
+	/// `bounds` grown to hold the vertices with any weights between -1 and 1 blended in
+	pub(crate) fn bounds(bounds: BoundingSphere, targets: &[MorphTarget]) -> BoundingSphere {
+		let count = targets.iter().map(|t| t.positions.len()).max().unwrap_or(0);
+		let reach = (0..count)
+			.map(|i| targets.iter().filter_map(|t| t.positions.get(i)).map(|p| p.magnitude()).sum::<f32>())
+			.fold(0.0, f32::max);
+		BoundingSphere { radius: bounds.radius + reach, ..bounds }
+	}
+}
+
+/// A mesh's morph targets on the GPU, with the position and normal offset of each vertex of each
+/// target next to each other
+pub(crate) struct Morphs {
+	pub deltas: Arc<ImmutableBuffer<[[f32; 4]]>>,
+	pub names: Vec<String>,
+}
+
+/// A mesh's index buffer, which holds either 16 or 32-bit indices
+#[derive(Clone)]
+pub enum IndexBuffer {
+	U16(Arc<ImmutableBuffer<[u16]>>),
+	U32(Arc<ImmutableBuffer<[u32]>>),
+}
+
+impl IndexBuffer {
+	pub fn len(&self) -> usize {
+		match *self {
+			IndexBuffer::U16(ref buffer) => buffer.len(),
+			IndexBuffer::U32(ref buffer) => buffer.len(),
+		}
+	}
+}
+
+/// How a mesh's vertices are put together into primitives. Vertices with a zero normal, which
+/// points and lines usually have, are drawn without lighting.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Topology {
+	PointList,
+	LineList,
+	/// In an indexed strip, the largest index value (`u16::MAX` or `u32::MAX`) starts a new strip
+	LineStrip,
+	TriangleList,
+	/// See `LineStrip` for restarting the strip
+	TriangleStrip,
+}
+
+impl Topology {
+	pub fn primitive(self) -> PrimitiveTopology {
+		match self {
+			Topology::PointList => PrimitiveTopology::PointList,
+			Topology::LineList => PrimitiveTopology::LineList,
+			Topology::LineStrip => PrimitiveTopology::LineStrip,
+			Topology::TriangleList => PrimitiveTopology::TriangleList,
+			Topology::TriangleStrip => PrimitiveTopology::TriangleStrip,
+		}
+	}
+	
+	/// Whether primitive restart should be enabled in the pipeline
+	pub fn is_strip(self) -> bool {
+		self == Topology::LineStrip || self == Topology::TriangleStrip
+	}
+	
+	/// Whether the pipeline needs a dynamic line width
+	pub fn is_line(self) -> bool {
+		self == Topology::LineList || self == Topology::LineStrip
+	}
+	
+	/// Whether `count` vertices or indices make up whole primitives
+	pub(crate) fn fits(self, count: usize) -> bool {
+		match self {
+			Topology::LineList => count % 2 == 0,
+			Topology::TriangleList => count % 3 == 0,
+			_ => true,
+		}
+	}
+}
+
+impl Default for Topology {
+	fn default() -> Self {
+		Topology::TriangleList
+	}
+}
+
+/// The types an index buffer can be made of
+pub trait MeshIndex: Index + Copy + Send + Sync + 'static {
+	/// The largest value, which restarts strips
+	const MAX: u32;
+	
+	fn to_u32(self) -> u32;
+	fn wrap(buffer: Arc<ImmutableBuffer<[Self]>>) -> IndexBuffer;
+}
+
+impl MeshIndex for u16 {
+	const MAX: u32 = 0xFFFF;
+	
+	fn to_u32(self) -> u32 {
+		self as u32
+	}
+	
+	fn wrap(buffer: Arc<ImmutableBuffer<[u16]>>) -> IndexBuffer {
+		IndexBuffer::U16(buffer)
+	}
+}
+
+impl MeshIndex for u32 {
+	const MAX: u32 = 0xFFFF_FFFF;
+	
+	fn to_u32(self) -> u32 {
+		self
+	}
+	
+	fn wrap(buffer: Arc<ImmutableBuffer<[u32]>>) -> IndexBuffer {
+		IndexBuffer::U32(buffer)
+	}
 }
 
 impl fmt::Debug for Mesh {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		write!(f, "Mesh {{ verts: ImmutableBuffer, indices: {} }}", if self.indices.is_some() {
-			"Some(ImmutableBuffer)"
+		match self.buffers {
+			Buffers::Immutable { vertex_count, ref indices, .. } => {
+				write!(f, "Mesh {{ verts: {}, indices: {}, bounds: {:?} }}", vertex_count, match *indices {
+					Some(IndexBuffer::U16(ref buffer)) => format!("Some({} x u16)", buffer.len()),
+					Some(IndexBuffer::U32(ref buffer)) => format!("Some({} x u32)", buffer.len()),
+					None => "None".to_string(),
+				}, self.bounds)
+			},
+			Buffers::Dynamic(ref mesh) => write!(f, "Mesh {{ dynamic: {:?} }}", mesh),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+	pub verts: Vec<Vertex>,
+	pub indices: Vec<u32>,
+}
+
+impl MeshData {
+	pub fn new(verts: Vec<Vertex>, indices: Vec<u32>) -> Self {
+		MeshData {
+			verts,
+			indices,
+		}
+	}
+	
+	/// The indices of the triangle list, which are every vertex in order for data without indices
+	pub(crate) fn triangle_indices<'a>(&'a self) -> Cow<'a, [u32]> {
+		if self.indices.is_empty() {
+			Cow::Owned((0..self.verts.len() as u32).collect())
 		} else {
-			"None"
-		})
+			Cow::Borrowed(&self.indices)
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Material {
+	/// Multiplied with the vertex colors and the texture
+	pub color: Color,
+	pub texture: Option<Arc<Texture>>,
+	/// Tangent space normal map, which should be uploaded with `Texture::from_rgba_linear`. Only
+	/// used for meshes with tangents.
+	pub normal_map: Option<Arc<Texture>>,
+	/// Size in pixels of each point for meshes with `Topology::PointList`
+	pub point_size: f32,
+	/// Width in pixels of lines. Widths other than 1 need the `wide_lines` device feature and are
+	/// ignored without it.
+	pub line_width: f32,
+}
+
+impl Default for Material {
+	fn default() -> Self {
+		Material {
+			color: Color::white(),
+			texture: None,
+			normal_map: None,
+			point_size: 1.0,
+			line_width: 1.0,
+		}
 	}
 }
 
+/// Processing applied by `Mesh::from_data_with` before the data is uploaded
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MeshOptions {
+	/// Reorder triangles and vertices to make better use of the GPU's caches, see `geometry::optimize`
+	pub optimize: bool,
+	/// Keep a copy of the vertices and indices on the CPU, so `Mesh::verts` and friends work
+	/// without a `Mesh::read_back`
+	pub keep_data: bool,
+	/// Store the indices as `u16` when every vertex can be reached with one, halving the size of
+	/// the index buffer. `0xFFFF` is left for restarting strips, so this needs fewer than 65535
+	/// vertices.
+	pub compact_indices: bool,
+}
+
+/// A simpler version of an object's mesh, see `Object::add_lod`
+#[derive(Debug)]
+pub struct Lod {
+	pub mesh: Box<Mesh>,
+	/// Used once the object covers less than this fraction of the viewport's height
+	pub screen_size: f32,
+}
+
 pub struct Object {
 	pub mesh: Box<Mesh>,
+	/// Model matrix applied to the mesh's vertices
+	pub transform: Matrix4<f32>,
+	pub material: Material,
+	/// Ordered from most to least detailed
+	pub lods: Vec<Lod>,
+	/// Poses the mesh if it's made of `SkinnedVertex`, which is otherwise drawn in its bind pose.
+	/// See `set_skin` for making sure the skeleton has every joint the mesh uses.
+	pub skin: Option<Skin>,
+	/// How much of each of the mesh's morph targets is blended in. Missing weights count as zero.
+	pub morph_weights: Vec<f32>,
+	/// Bit mask of the layers the object is on, drawn by views whose mask shares any of them
+	pub layers: u32,
 }
 
 impl Object {
 	pub fn from_mesh(m: Mesh) -> Self {
 		Object {
 			mesh: Box::new(m),
+			transform: Matrix4::identity(),
+			material: Material::default(),
+			lods: Vec::new(),
+			skin: None,
+			morph_weights: Vec::new(),
+			layers: 1,
+		}
+	}
+	
+	/// Adds a mesh to draw instead of the full one once the object covers less than `screen_size`
+	/// of the viewport's height. The meshes for `geometry::lod_chain` can be added in order.
+	pub fn add_lod(&mut self, mesh: Mesh, screen_size: f32) {
+		let index = self.lods.iter().position(|l| l.screen_size < screen_size).unwrap_or(self.lods.len());
+		self.lods.insert(index, Lod {
+			mesh: Box::new(mesh),
+			screen_size,
+		});
+	}
+	
+	/// Sets the skin, failing if its skeleton has fewer joints than the mesh or one of its levels
+	/// of detail is attached to
+	pub fn set_skin(&mut self, skin: Skin) -> Result<(), ()> {
+		let needed = self.lods.iter().map(|l| l.mesh.joint_count()).fold(self.mesh.joint_count(), usize::max);
+		if skin.skeleton.joints().len() < needed {
+			return Err(());
+		}
+		self.skin = Some(skin);
+		Ok(())
+	}
+	
+	/// The mesh that should be drawn when the object is seen by `camera`
+	pub fn mesh_for(&self, camera: &Camera) -> &Mesh {
+		if self.lods.is_empty() {
+			return &self.mesh;
+		}
+		let size = camera.projected_size(&self.mesh.bounds().transform(&self.transform));
+		let mut mesh = &self.mesh;
+		for lod in &self.lods {
+			if size >= lod.screen_size {
+				break;
+			}
+			mesh = &lod.mesh;
 		}
+		mesh
 	}
 }
 
 impl Mesh {
-	pub fn new(internal: &RenderInternal, verts: Vec<Vertex>, indices: Vec<u32>) -> Result<Self, ()> {
-		// Make sure the indices represent triangles well
-		if indices.len() % 3 != 0 {
+	/// Uploads an indexed triangle list. Any vertex type with a registered pipeline can be used,
+	/// see `Renderer::add_vertex_format`, and the indices can be `u16` or `u32`.
+	pub fn new<V: MeshVertex, I: MeshIndex>(internal: &RenderInternal, verts: Vec<V>, indices: Vec<I>) -> Result<Self, ()> {
+		Mesh::new_with_topology(internal, verts, indices, Topology::TriangleList)
+	}
+	
+	/// Like `new`, but the indices make up `topology` instead of a triangle list
+	pub fn new_with_topology<V, I>(internal: &RenderInternal, verts: Vec<V>, indices: Vec<I>, topology: Topology) -> Result<Self, ()>
+		where V: MeshVertex, I: MeshIndex
+	{
+		// Make sure the indices represent whole primitives
+		if !topology.fits(indices.len()) {
 			return Err(())
 		}
 		
 		// Make sure none of the indices refer to a vertex that isn't available
 		for i in &indices {
-			if *i as usize >= verts.len() {
+			let i = i.to_u32();
+			if i as usize >= verts.len() && !(topology.is_strip() && i == I::MAX) {
 				return Err(())
 			}
 		}
 		
-		let (vbuf, _) = ImmutableBuffer::from_iter(verts.into_iter(), BufferUsage::all(), internal.queue.clone()).unwrap();
 		let (ibuf, _) = ImmutableBuffer::from_iter(indices.into_iter(), BufferUsage::all(), internal.queue.clone()).unwrap();
-		
-		Ok(Mesh {
-			verts: vbuf,
-			indices: Some(ibuf),
-		})
+		Ok(Mesh::upload(internal, verts, Some(I::wrap(ibuf)), topology))
+	}
+	
+	/// Uploads a triangle list. Data without indices is drawn in the order the vertices are in.
+	pub fn from_data(internal: &RenderInternal, data: MeshData) -> Result<Self, ()> {
+		if data.indices.is_empty() {
+			return Mesh::new_pure_with_topology(internal, data.verts, Topology::TriangleList)
+		}
+		Mesh::new(internal, data.verts, data.indices)
 	}
 	
-	pub fn new_pure(internal: &RenderInternal, verts: Vec<Vertex>) -> Self {
+	fn upload<V: MeshVertex>(internal: &RenderInternal, verts: Vec<V>, indices: Option<IndexBuffer>, topology: Topology) -> Self {
+		let bounds = BoundingSphere::from_verts(&verts);
+		let joints = verts.iter().map(|v| v.joint_count()).max().unwrap_or(0);
+		let vertex_count = verts.len();
 		let (vbuf, _) = ImmutableBuffer::from_iter(verts.into_iter(), BufferUsage::all(), internal.queue.clone()).unwrap();
 		
 		Mesh {
-			verts: vbuf,
-			indices: None,
+			buffers: Buffers::Immutable {
+				verts: vbuf.clone(),
+				typed_verts: vbuf,
+				vertex_count,
+				indices,
+			},
+			format: TypeId::of::<V>(),
+			topology,
+			morphs: None,
+			bounds,
+			joints,
+			data: None,
+		}
+	}
+	
+	/// Adds morph targets to a mesh of `td::Vertex`. Fails for other vertex types, if there are
+	/// more than `MAX_MORPH_TARGETS`, or if a target doesn't have an offset for every vertex. The
+	/// bounds grow to hold the mesh with any weights between -1 and 1.
+	pub fn with_morph_targets(mut self, internal: &RenderInternal, targets: Vec<MorphTarget>) -> Result<Self, ()> {
+		let count = self.vertex_count();
+		let immutable = match self.buffers {
+			Buffers::Immutable { .. } => true,
+			Buffers::Dynamic(_) => false,
+		};
+		if !immutable || self.format != TypeId::of::<Vertex>() || targets.len() > MAX_MORPH_TARGETS {
+			return Err(());
+		}
+		if targets.iter().any(|t| t.positions.len() != count || t.normals.as_ref().map_or(false, |n| n.len() != count)) {
+			return Err(());
+		}
+		if targets.is_empty() {
+			self.morphs = None;
+			return Ok(self);
+		}
+		
+		let mut deltas = Vec::with_capacity(targets.len() * count * 2);
+		for target in &targets {
+			for (i, p) in target.positions.iter().enumerate() {
+				let n = target.normals.as_ref().map_or(Vec3::zero(), |n| n[i]);
+				deltas.push([p.x, p.y, p.z, 0.0]);
+				deltas.push([n.x, n.y, n.z, 0.0]);
+			}
+		}
+		let (deltas, _) = ImmutableBuffer::from_iter(deltas.into_iter(), BufferUsage::all(), internal.queue.clone()).unwrap();
+		self.bounds = MorphTarget::bounds(self.bounds, &targets);
+		self.morphs = Some(Morphs {
+			deltas,
+			names: targets.into_iter().map(|t| t.name).collect(),
+		});
+		Ok(self)
+	}
+	
+	/// The names of the morph targets, in the order `Object::morph_weights` refers to them
+	pub fn morph_targets(&self) -> &[String] {
+		match self.morphs {
+			Some(ref morphs) => &morphs.names,
+			None => &[],
+		}
+	}
+	
+	pub fn morph_target(&self, name: &str) -> Option<usize> {
+		self.morph_targets().iter().position(|n| n == name)
+	}
+	
+	pub fn from_data_with(internal: &RenderInternal, data: MeshData, options: &MeshOptions) -> Result<Self, ()> {
+		Mesh::from_data_with_targets(internal, data, Vec::new(), options)
+	}
+	
+	/// Like `from_data_with`, but also adds morph targets that follow the vertices of `data`
+	/// through any reordering, see `with_morph_targets`
+	pub fn from_data_with_targets(internal: &RenderInternal, mut data: MeshData, mut targets: Vec<MorphTarget>, options: &MeshOptions) -> Result<Self, ()> {
+		// Check the indices and offsets before anything tries to follow them
+		let count = data.verts.len();
+		if data.indices.iter().any(|&i| i as usize >= count) {
+			return Err(())
+		}
+		if targets.iter().any(|t| t.positions.len() != count || t.normals.as_ref().map_or(false, |n| n.len() != count)) {
+			return Err(())
+		}
+		if options.optimize {
+			let sources = geometry::optimize_mapped(&mut data);
+			for target in &mut targets {
+				target.remap(&sources);
+			}
+		}
+		let kept = if options.keep_data { Some(data.clone()) } else { None };
+		let mut mesh = if options.compact_indices && !data.indices.is_empty() && data.verts.len() < <u16 as MeshIndex>::MAX as usize {
+			let indices = data.indices.iter().map(|&i| i as u16).collect::<Vec<_>>();
+			Mesh::new(internal, data.verts, indices)?
+		} else {
+			Mesh::from_data(internal, data)?
+		};
+		mesh.data = kept;
+		if targets.is_empty() {
+			return Ok(mesh);
+		}
+		mesh.with_morph_targets(internal, targets)
+	}
+	
+	pub fn new_pure<V: MeshVertex>(internal: &RenderInternal, verts: Vec<V>) -> Self {
+		Mesh::upload(internal, verts, None, Topology::TriangleList)
+	}
+	
+	/// Like `new_pure`, but the vertices make up `topology` instead of a triangle list
+	pub fn new_pure_with_topology<V: MeshVertex>(internal: &RenderInternal, verts: Vec<V>, topology: Topology) -> Result<Self, ()> {
+		if !topology.fits(verts.len()) {
+			return Err(())
+		}
+		Ok(Mesh::upload(internal, verts, None, topology))
+	}
+	
+	pub(crate) fn from_dynamic<V: MeshVertex>(mesh: DynamicMesh<V>) -> Self {
+		Mesh {
+			bounds: mesh.bounds(),
+			format: TypeId::of::<V>(),
+			topology: mesh.topology(),
+			buffers: Buffers::Dynamic(Box::new(mesh)),
+			morphs: None,
+			joints: 0,
+			data: None,
+		}
+	}
+	
+	/// A sphere containing every vertex, in the mesh's own space
+	pub fn bounds(&self) -> BoundingSphere {
+		match self.buffers {
+			Buffers::Immutable { .. } => self.bounds,
+			Buffers::Dynamic(ref mesh) => mesh.bounds(),
+		}
+	}
+	
+	/// Like `new_pure`, but vertices within `epsilon` of each other are first merged into an
+	/// index buffer. An `epsilon` of zero only merges identical vertices.
+	pub fn new_pure_welded(internal: &RenderInternal, verts: Vec<Vertex>, epsilon: f32) -> (Self, WeldReport) {
+		// Leftover vertices that don't form a whole triangle wouldn't be drawn anyway
+		let count = verts.len() as u32 / 3 * 3;
+		let mut data = MeshData::new(verts, (0..count).collect());
+		let report = geometry::weld_epsilon(&mut data, epsilon);
+		(Mesh::from_data(internal, data).unwrap(), report)
+	}
+	
+	/// Copies the vertices and indices back from the GPU so the CPU-side accessors can be used.
+	/// Does nothing if they're already available. Only works for meshes of `td::Vertex`; dynamic
+	/// meshes can't be read back this way, use `DynamicMesh::read` instead.
+	pub fn read_back(&mut self, internal: &RenderInternal) -> Result<(), ()> {
+		if self.data.is_some() {
+			return Ok(());
+		}
+		let data = match self.buffers {
+			Buffers::Immutable { ref typed_verts, ref indices, .. } => {
+				let verts = typed_verts.clone().downcast::<ImmutableBuffer<[Vertex]>>().map_err(|_| ())?;
+				let filler = Vertex::new(0.0, 0.0, 0.0, 1.0, Color::black());
+				MeshData::new(
+					read_buffer(internal, &verts, filler)?,
+					match *indices {
+						Some(IndexBuffer::U16(ref indices)) => {
+							// Restarts are stored as `u32::MAX` on the CPU, whatever the width on the GPU
+							let strip = self.topology.is_strip();
+							read_buffer(internal, indices, 0)?.into_iter()
+								.map(|i| if strip && i == ::std::u16::MAX { ::std::u32::MAX } else { i as u32 })
+								.collect()
+						},
+						Some(IndexBuffer::U32(ref indices)) => read_buffer(internal, indices, 0)?,
+						None => Vec::new(),
+					},
+				)
+			},
+			Buffers::Dynamic(_) => return Err(()),
+		};
+		self.data = Some(data);
+		Ok(())
+	}
+	
+	/// Frees the CPU-side copy of the mesh, if there is one
+	pub fn discard_data(&mut self) {
+		self.data = None;
+	}
+	
+	/// The CPU-side copy of the mesh, if it was kept or read back
+	pub fn data(&self) -> Option<&MeshData> {
+		self.data.as_ref()
+	}
+	
+	pub fn verts(&self) -> Option<&[Vertex]> {
+		self.data.as_ref().map(|data| &data.verts[..])
+	}
+	
+	/// The indices, if they're available and the mesh has an index buffer
+	pub fn indices(&self) -> Option<&[u32]> {
+		if !self.is_indexed() {
+			return None;
+		}
+		self.data.as_ref().map(|data| &data.indices[..])
+	}
+	
+	/// The corners of every triangle, if the CPU-side copy is available. Strips are unrolled
+	/// with their winding order fixed up, and points and lines have no triangles.
+	pub fn triangles<'a>(&'a self) -> Option<impl Iterator<Item = [Vertex; 3]> + 'a> {
+		let data = self.data.as_ref()?;
+		Some(self.triangle_corners(data).into_iter().map(move |t| {
+			[data.verts[t[0] as usize], data.verts[t[1] as usize], data.verts[t[2] as usize]]
+		}))
+	}
+	
+	fn triangle_corners(&self, data: &MeshData) -> Vec<[u32; 3]> {
+		let order = if self.is_indexed() {
+			data.indices.clone()
+		} else {
+			(0..data.verts.len() as u32).collect()
+		};
+		match self.topology {
+			Topology::TriangleList => order.chunks(3)
+				.filter(|t| t.len() == 3)
+				.map(|t| [t[0], t[1], t[2]])
+				.collect(),
+			Topology::TriangleStrip => order.split(|&i| i == ::std::u32::MAX)
+				.flat_map(|strip| (2..strip.len()).map(move |k| if k % 2 == 0 {
+					[strip[k - 2], strip[k - 1], strip[k]]
+				} else {
+					[strip[k - 1], strip[k - 2], strip[k]]
+				}))
+				.collect(),
+			_ => Vec::new(),
+		}
+	}
+	
+	pub fn topology(&self) -> Topology {
+		self.topology
+	}
+	
+	/// How many joints a skeleton needs to pose the mesh, see `MeshVertex::joint_count`
+	pub fn joint_count(&self) -> usize {
+		match self.buffers {
+			Buffers::Immutable { .. } => self.joints,
+			Buffers::Dynamic(ref mesh) => mesh.joint_count(),
+		}
+	}
+	
+	pub fn is_indexed(&self) -> bool {
+		match self.buffers {
+			Buffers::Immutable { ref indices, .. } => indices.is_some(),
+			Buffers::Dynamic(ref mesh) => mesh.index_count().is_some(),
+		}
+	}
+	
+	/// The number of vertices, which is known even without the CPU-side copy
+	pub fn vertex_count(&self) -> usize {
+		match self.buffers {
+			Buffers::Immutable { vertex_count, .. } => vertex_count,
+			Buffers::Dynamic(ref mesh) => mesh.vertex_count(),
+		}
+	}
+	
+	/// The number of triangles drawn, which is known even without the CPU-side copy. Restarts in
+	/// an indexed triangle strip are only accounted for once the CPU-side copy is available.
+	pub fn triangle_count(&self) -> usize {
+		let corners = match self.buffers {
+			Buffers::Immutable { vertex_count, ref indices, .. } => indices.as_ref().map_or(vertex_count, |i| i.len()),
+			Buffers::Dynamic(ref mesh) => mesh.index_count().unwrap_or(mesh.vertex_count()),
+		};
+		match self.topology {
+			Topology::TriangleList => corners / 3,
+			Topology::TriangleStrip => match self.data {
+				Some(ref data) => self.triangle_corners(data).len(),
+				None => corners.saturating_sub(2),
+			},
+			_ => 0,
 		}
 	}
 }
 
+/// Copies a GPU-only buffer into host-visible memory and waits for it to arrive
+fn read_buffer<T>(internal: &RenderInternal, buffer: &Arc<ImmutableBuffer<[T]>>, filler: T) -> Result<Vec<T>, ()>
+	where T: Copy + Send + Sync + 'static
+{
+	let len = buffer.len();
+	let target = CpuAccessibleBuffer::from_iter(internal.device.clone(), BufferUsage::all(), (0..len).map(|_| filler))
+		.map_err(|_| ())?;
+	let commands = AutoCommandBufferBuilder::primary_one_time_submit(internal.device.clone(), internal.queue.family())
+		.map_err(|_| ())?
+		.copy_buffer(buffer.clone(), target.clone())
+		.map_err(|_| ())?
+		.build()
+		.map_err(|_| ())?;
+	commands.execute(internal.queue.clone()).map_err(|_| ())?
+		.then_signal_fence_and_flush().map_err(|_| ())?
+		.wait(None).map_err(|_| ())?;
+	let contents = target.read().map_err(|_| ())?;
+	Ok(contents.to_vec())
+}
@@ -0,0 +1,307 @@
+//! Generators for common shapes, with normals and texture coordinates.
+//!
+//! Every shape is centered on the origin with Y up, and is returned as `MeshData` so it can be
+//! processed further before being uploaded with `Mesh::from_data`. Front faces wind counter
+//! clockwise.
+
+use std::f32::consts::PI;
+use std::collections::HashMap;
+
+use cgmath::{Vector3, InnerSpace, ElementWise};
+
+use obj::MeshData;
+use td::{Vertex, Vec3, Color};
+
+/// A box with the given side lengths. Each face has its own vertices so the edges stay sharp.
+pub fn cuboid(size: Vec3, color: Color) -> MeshData {
+	let half = size / 2.0;
+	let faces = [
+		(Vec3::unit_x(), -Vec3::unit_z(), Vec3::unit_y()),
+		(-Vec3::unit_x(), Vec3::unit_z(), Vec3::unit_y()),
+		(Vec3::unit_y(), Vec3::unit_x(), -Vec3::unit_z()),
+		(-Vec3::unit_y(), Vec3::unit_x(), Vec3::unit_z()),
+		(Vec3::unit_z(), Vec3::unit_x(), Vec3::unit_y()),
+		(-Vec3::unit_z(), -Vec3::unit_x(), Vec3::unit_y()),
+	];
+	
+	let mut data = MeshData::default();
+	for &(n, u, v) in &faces {
+		let base = data.verts.len() as u32;
+		for &(s, t) in &[(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+			let p = (n + u * s + v * t).mul_element_wise(half);
+			data.verts.push(vertex(p, n, [(s + 1.0) / 2.0, (1.0 - t) / 2.0], color));
+		}
+		data.indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+	}
+	data
+}
+
+/// A flat square in the XZ plane facing up
+pub fn plane(width: f32, depth: f32, color: Color) -> MeshData {
+	grid(width, depth, 1, 1, color)
+}
+
+/// A plane in the XZ plane facing up, subdivided into `x_segments` by `z_segments` cells
+pub fn grid(width: f32, depth: f32, x_segments: u32, z_segments: u32, color: Color) -> MeshData {
+	let (xs, zs) = (x_segments.max(1), z_segments.max(1));
+	let mut data = MeshData::default();
+	
+	for j in 0..zs + 1 {
+		for i in 0..xs + 1 {
+			let (u, v) = (i as f32 / xs as f32, j as f32 / zs as f32);
+			let p = Vec3::new((u - 0.5) * width, 0.0, (v - 0.5) * depth);
+			data.verts.push(vertex(p, Vec3::unit_y(), [u, v], color));
+		}
+	}
+	for j in 0..zs {
+		for i in 0..xs {
+			let a = j * (xs + 1) + i;
+			let (b, c, d) = (a + 1, a + xs + 2, a + xs + 1);
+			data.indices.extend_from_slice(&[a, c, b, a, d, c]);
+		}
+	}
+	data
+}
+
+/// A sphere made of `rings` bands of latitude and `segments` slices of longitude
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32, color: Color) -> MeshData {
+	let rings = rings.max(2);
+	let profile = (0..rings + 1).map(|r| {
+		let phi = PI * r as f32 / rings as f32;
+		Ring {
+			radius: radius * phi.sin(),
+			y: radius * phi.cos(),
+			normal: [phi.sin(), phi.cos()],
+			v: r as f32 / rings as f32,
+		}
+	}).collect::<Vec<_>>();
+	
+	let mut data = MeshData::default();
+	lathe(&mut data, &profile, segments, color);
+	data
+}
+
+/// A sphere made by repeatedly subdividing an icosahedron, which spreads the vertices much more
+/// evenly than `uv_sphere`. Every subdivision multiplies the triangle count by four.
+pub fn icosphere(radius: f32, subdivisions: u32, color: Color) -> MeshData {
+	let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+	let mut points = vec![
+		Vec3::new(-1.0, t, 0.0), Vec3::new(1.0, t, 0.0), Vec3::new(-1.0, -t, 0.0), Vec3::new(1.0, -t, 0.0),
+		Vec3::new(0.0, -1.0, t), Vec3::new(0.0, 1.0, t), Vec3::new(0.0, -1.0, -t), Vec3::new(0.0, 1.0, -t),
+		Vec3::new(t, 0.0, -1.0), Vec3::new(t, 0.0, 1.0), Vec3::new(-t, 0.0, -1.0), Vec3::new(-t, 0.0, 1.0),
+	].into_iter().map(|p| p.normalize()).collect::<Vec<_>>();
+	let mut indices: Vec<u32> = vec![
+		0, 11, 5, 0, 5, 1, 0, 1, 7, 0, 7, 10, 0, 10, 11,
+		1, 5, 9, 5, 11, 4, 11, 10, 2, 10, 7, 6, 7, 1, 8,
+		3, 9, 4, 3, 4, 2, 3, 2, 6, 3, 6, 8, 3, 8, 9,
+		4, 9, 5, 2, 4, 11, 6, 2, 10, 8, 6, 7, 9, 8, 1,
+	];
+	
+	for _ in 0..subdivisions {
+		let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+		let mut next = Vec::with_capacity(indices.len() * 4);
+		for tri in indices.chunks(3) {
+			let mut mid = [0; 3];
+			for i in 0..3 {
+				let (a, b) = (tri[i], tri[(i + 1) % 3]);
+				let key = if a < b { (a, b) } else { (b, a) };
+				mid[i] = *midpoints.entry(key).or_insert_with(|| {
+					points.push(((points[a as usize] + points[b as usize]) / 2.0).normalize());
+					(points.len() - 1) as u32
+				});
+			}
+			next.extend_from_slice(&[tri[0], mid[0], mid[2], tri[1], mid[1], mid[0], tri[2], mid[2], mid[1], mid[0], mid[1], mid[2]]);
+		}
+		indices = next;
+	}
+	
+	// Texture coordinates are a plain spherical projection, so they stretch across the seam
+	let verts = points.into_iter().map(|n| {
+		let uv = [0.5 + n.z.atan2(n.x) / (2.0 * PI), n.y.max(-1.0).min(1.0).acos() / PI];
+		vertex(n * radius, n, uv, color)
+	}).collect();
+	MeshData::new(verts, indices)
+}
+
+/// A capped cylinder standing on the XZ plane's origin
+pub fn cylinder(radius: f32, height: f32, segments: u32, color: Color) -> MeshData {
+	let h = height / 2.0;
+	let mut data = MeshData::default();
+	lathe(&mut data, &[
+		Ring { radius, y: h, normal: [1.0, 0.0], v: 0.0 },
+		Ring { radius, y: -h, normal: [1.0, 0.0], v: 1.0 },
+	], segments, color);
+	disc(&mut data, h, radius, segments, true, color);
+	disc(&mut data, -h, radius, segments, false, color);
+	data
+}
+
+/// A cone with its base at the bottom and its tip at the top
+pub fn cone(radius: f32, height: f32, segments: u32, color: Color) -> MeshData {
+	let h = height / 2.0;
+	let slope = Vector3::new(height, radius, 0.0).normalize();
+	let mut data = MeshData::default();
+	lathe(&mut data, &[
+		Ring { radius: 0.0, y: h, normal: [slope.x, slope.y], v: 0.0 },
+		Ring { radius, y: -h, normal: [slope.x, slope.y], v: 1.0 },
+	], segments, color);
+	disc(&mut data, -h, radius, segments, false, color);
+	data
+}
+
+/// A cylinder with hemispherical ends. `height` is the total height including the ends, and
+/// `rings` is the number of bands in each hemisphere.
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32, color: Color) -> MeshData {
+	let rings = rings.max(1);
+	let h = (height / 2.0 - radius).max(0.0);
+	let mut profile = Vec::with_capacity(rings as usize * 2 + 2);
+	for &(center, start) in &[(h, 0.0), (-h, PI / 2.0)] {
+		for r in 0..rings + 1 {
+			let phi = start + PI / 2.0 * r as f32 / rings as f32;
+			profile.push(Ring {
+				radius: radius * phi.sin(),
+				y: center + radius * phi.cos(),
+				normal: [phi.sin(), phi.cos()],
+				v: 0.0,
+			});
+		}
+	}
+	fill_v(&mut profile);
+	
+	let mut data = MeshData::default();
+	lathe(&mut data, &profile, segments, color);
+	data
+}
+
+/// A ring around the Y axis. `major_radius` is the distance from the center to the middle of
+/// the tube, and `minor_radius` is the radius of the tube.
+pub fn torus(major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32, color: Color) -> MeshData {
+	let (us, vs) = (major_segments.max(3), minor_segments.max(3));
+	let mut data = MeshData::default();
+	
+	for i in 0..us + 1 {
+		let u = 2.0 * PI * i as f32 / us as f32;
+		for j in 0..vs + 1 {
+			let v = 2.0 * PI * j as f32 / vs as f32;
+			let n = Vec3::new(v.cos() * u.cos(), v.sin(), v.cos() * u.sin());
+			let p = Vec3::new(major_radius * u.cos(), 0.0, major_radius * u.sin()) + n * minor_radius;
+			data.verts.push(vertex(p, n, [i as f32 / us as f32, j as f32 / vs as f32], color));
+		}
+	}
+	for i in 0..us {
+		for j in 0..vs {
+			let a = i * (vs + 1) + j;
+			let (b, c, d) = (a + 1, a + vs + 2, a + vs + 1);
+			data.indices.extend_from_slice(&[a, b, c, a, c, d]);
+		}
+	}
+	data
+}
+
+/// An arrow pointing up along +Y from the origin, made of a cylindrical shaft and a conical head
+pub fn arrow(length: f32, shaft_radius: f32, head_radius: f32, head_length: f32, segments: u32, color: Color) -> MeshData {
+	let head_length = head_length.min(length);
+	let neck = length - head_length;
+	let slope = Vector3::new(head_length, head_radius, 0.0).normalize();
+	
+	let mut data = MeshData::default();
+	lathe(&mut data, &[
+		Ring { radius: 0.0, y: length, normal: [slope.x, slope.y], v: 0.0 },
+		Ring { radius: head_radius, y: neck, normal: [slope.x, slope.y], v: 1.0 },
+	], segments, color);
+	// The underside of the head
+	lathe(&mut data, &[
+		Ring { radius: head_radius, y: neck, normal: [0.0, -1.0], v: 0.0 },
+		Ring { radius: shaft_radius, y: neck, normal: [0.0, -1.0], v: 1.0 },
+	], segments, color);
+	lathe(&mut data, &[
+		Ring { radius: shaft_radius, y: neck, normal: [1.0, 0.0], v: 0.0 },
+		Ring { radius: shaft_radius, y: 0.0, normal: [1.0, 0.0], v: 1.0 },
+	], segments, color);
+	disc(&mut data, 0.0, shaft_radius, segments, false, color);
+	data
+}
+
+fn vertex(p: Vec3, n: Vec3, uv: [f32; 2], color: Color) -> Vertex {
+	let mut v = Vertex::new(p.x, p.y, p.z, 1.0, color);
+	v.a_Normal = *n.as_ref();
+	v.a_TexCoord = uv;
+	v
+}
+
+/// One row of a surface of revolution. `normal` is given as (radial, vertical) components.
+struct Ring {
+	radius: f32,
+	y: f32,
+	normal: [f32; 2],
+	v: f32,
+}
+
+/// Sets the texture coordinates along a profile proportionally to its length
+fn fill_v(profile: &mut [Ring]) {
+	let mut lengths = vec![0.0];
+	for pair in profile.windows(2) {
+		let d = ((pair[1].radius - pair[0].radius).powi(2) + (pair[1].y - pair[0].y).powi(2)).sqrt();
+		let last = *lengths.last().unwrap();
+		lengths.push(last + d);
+	}
+	let total = lengths.last().cloned().unwrap_or(0.0).max(::std::f32::EPSILON);
+	for (ring, length) in profile.iter_mut().zip(lengths) {
+		ring.v = length / total;
+	}
+}
+
+/// Sweeps a profile, given from top to bottom, around the Y axis
+fn lathe(data: &mut MeshData, profile: &[Ring], segments: u32, color: Color) {
+	let segments = segments.max(3);
+	let base = data.verts.len() as u32;
+	
+	for ring in profile {
+		for s in 0..segments + 1 {
+			let theta = 2.0 * PI * s as f32 / segments as f32;
+			let (cos, sin) = (theta.cos(), theta.sin());
+			let p = Vec3::new(ring.radius * cos, ring.y, ring.radius * sin);
+			let n = Vec3::new(ring.normal[0] * cos, ring.normal[1], ring.normal[0] * sin);
+			data.verts.push(vertex(p, n, [s as f32 / segments as f32, ring.v], color));
+		}
+	}
+	
+	// Rows that collapse to a point only need one of the two triangles. Poles computed with
+	// trigonometry are only close to zero, so anything tiny next to the widest row counts.
+	let widest = profile.iter().fold(0.0f32, |w, ring| w.max(ring.radius.abs()));
+	let point = |ring: &Ring| ring.radius.abs() <= widest * 1e-6;
+	for r in 0..profile.len().saturating_sub(1) {
+		for s in 0..segments {
+			let a = base + r as u32 * (segments + 1) + s;
+			let (b, c, d) = (a + segments + 1, a + segments + 2, a + 1);
+			if !point(&profile[r]) {
+				data.indices.extend_from_slice(&[a, d, c]);
+			}
+			if !point(&profile[r + 1]) {
+				data.indices.extend_from_slice(&[a, c, b]);
+			}
+		}
+	}
+}
+
+/// A flat cap at height `y`, facing up or down
+fn disc(data: &mut MeshData, y: f32, radius: f32, segments: u32, up: bool, color: Color) {
+	let segments = segments.max(3);
+	let n = if up { Vec3::unit_y() } else { -Vec3::unit_y() };
+	let center = data.verts.len() as u32;
+	data.verts.push(vertex(Vec3::new(0.0, y, 0.0), n, [0.5, 0.5], color));
+	
+	for s in 0..segments + 1 {
+		let theta = 2.0 * PI * s as f32 / segments as f32;
+		let (cos, sin) = (theta.cos(), theta.sin());
+		data.verts.push(vertex(Vec3::new(radius * cos, y, radius * sin), n, [0.5 + 0.5 * cos, 0.5 + 0.5 * sin], color));
+	}
+	for s in 0..segments {
+		let (p, q) = (center + 1 + s, center + 2 + s);
+		if up {
+			data.indices.extend_from_slice(&[center, q, p]);
+		} else {
+			data.indices.extend_from_slice(&[center, p, q]);
+		}
+	}
+}
@@ -0,0 +1,405 @@
+//! Skeletal animation: joint hierarchies, keyframed clips, blending between them, and the joint
+//! matrices the skinning vertex shader uses to move `SkinnedVertex` meshes.
+
+use std::sync::Arc;
+
+use cgmath::{Matrix4, Vector3, Quaternion, SquareMatrix, InnerSpace, One, Zero};
+
+use td::*;
+use animation::Curve;
+
+/// The most joints a skeleton can have, limited by the size of the joint uniform buffer
+pub const MAX_JOINTS: usize = 64;
+
+#[derive(Copy, Clone, Debug)]
+#[allow(non_snake_case)]
+pub struct SkinnedVertex {
+	pub a_Pos: [f32; 4],
+	pub a_Color: [f32; 4],
+	pub a_Normal: [f32; 3],
+	pub a_TexCoord: [f32; 2],
+	pub a_Tangent: [f32; 4],
+	/// Up to four joints that move the vertex, as indices into the skeleton
+	pub a_Joints: [u32; 4],
+	/// How much each of `a_Joints` moves the vertex, adding up to one
+	pub a_Weights: [f32; 4],
+}
+
+impl_vertex!(SkinnedVertex, a_Pos, a_Color, a_Normal, a_TexCoord, a_Tangent, a_Joints, a_Weights);
+
+impl SkinnedVertex {
+	/// Attaches a vertex to joints. Joints past `MAX_JOINTS` can't be used, so they lose their
+	/// weight. The weights are scaled to add up to one, and a vertex without any weight follows
+	/// the first joint.
+	pub fn new(v: Vertex, mut joints: [u32; 4], mut weights: [f32; 4]) -> Self {
+		for (joint, weight) in joints.iter_mut().zip(weights.iter_mut()) {
+			if *joint as usize >= MAX_JOINTS {
+				*joint = 0;
+				*weight = 0.0;
+			}
+		}
+		let total: f32 = weights.iter().sum();
+		let weights = if total > 0.0 {
+			[weights[0] / total, weights[1] / total, weights[2] / total, weights[3] / total]
+		} else {
+			[1.0, 0.0, 0.0, 0.0]
+		};
+		SkinnedVertex {
+			a_Pos: v.a_Pos,
+			a_Color: v.a_Color,
+			a_Normal: v.a_Normal,
+			a_TexCoord: v.a_TexCoord,
+			a_Tangent: v.a_Tangent,
+			a_Joints: joints,
+			a_Weights: weights,
+		}
+	}
+}
+
+impl MeshVertex for SkinnedVertex {
+	fn position(&self) -> Vec3 {
+		Vector3::new(self.a_Pos[0], self.a_Pos[1], self.a_Pos[2])
+	}
+	
+	fn joint_count(&self) -> usize {
+		self.a_Joints.iter().zip(&self.a_Weights)
+			.filter(|&(_, &w)| w != 0.0)
+			.map(|(&j, _)| j as usize + 1)
+			.max()
+			.unwrap_or(0)
+	}
+}
+
+/// A translation, rotation and scale, applied to a point in reverse order
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Trs {
+	pub translation: Vec3,
+	pub rotation: Quaternion<f32>,
+	pub scale: Vec3,
+}
+
+impl Trs {
+	pub fn identity() -> Self {
+		Trs {
+			translation: Vector3::zero(),
+			rotation: Quaternion::one(),
+			scale: Vector3::new(1.0, 1.0, 1.0),
+		}
+	}
+	
+	pub fn matrix(&self) -> Matrix4<f32> {
+		Matrix4::from_translation(self.translation)
+			* Matrix4::from(self.rotation)
+			* Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+	}
+	
+	/// Interpolates towards `other`, taking the shorter way around for the rotation
+	pub fn lerp(&self, other: &Trs, t: f32) -> Trs {
+		Trs {
+			translation: self.translation + (other.translation - self.translation) * t,
+			rotation: nlerp(self.rotation, other.rotation, t),
+			scale: self.scale + (other.scale - self.scale) * t,
+		}
+	}
+}
+
+impl Default for Trs {
+	fn default() -> Self {
+		Trs::identity()
+	}
+}
+
+/// Normalized linear interpolation between rotations along the shorter arc
+pub fn nlerp(a: Quaternion<f32>, b: Quaternion<f32>, t: f32) -> Quaternion<f32> {
+	let b = if a.dot(b) < 0.0 { -b } else { b };
+	(a * (1.0 - t) + b * t).normalize()
+}
+
+#[derive(Clone, Debug)]
+pub struct Joint {
+	pub name: String,
+	/// Index of the parent joint, which has to come before this one in the skeleton
+	pub parent: Option<usize>,
+	/// The joint's transform relative to its parent when it isn't animated
+	pub rest: Trs,
+	/// Takes vertices from the mesh's space into the joint's space in the bind pose
+	pub inverse_bind: Matrix4<f32>,
+}
+
+impl Joint {
+	/// A joint whose inverse bind matrix still has to be set, e.g. with `Skeleton::bind_rest_pose`
+	pub fn new(name: &str, parent: Option<usize>, rest: Trs) -> Self {
+		Joint {
+			name: name.to_string(),
+			parent,
+			rest,
+			inverse_bind: Matrix4::identity(),
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Skeleton {
+	joints: Vec<Joint>,
+}
+
+impl Skeleton {
+	/// Fails if a joint comes before its parent or there are more than `MAX_JOINTS` joints
+	pub fn new(joints: Vec<Joint>) -> Result<Self, ()> {
+		if joints.len() > MAX_JOINTS {
+			return Err(());
+		}
+		for (i, joint) in joints.iter().enumerate() {
+			if joint.parent.map_or(false, |p| p >= i) {
+				return Err(());
+			}
+		}
+		Ok(Skeleton {
+			joints,
+		})
+	}
+	
+	pub fn joints(&self) -> &[Joint] {
+		&self.joints
+	}
+	
+	pub fn find(&self, name: &str) -> Option<usize> {
+		self.joints.iter().position(|j| j.name == name)
+	}
+	
+	pub fn rest_pose(&self) -> Pose {
+		Pose {
+			joints: self.joints.iter().map(|j| j.rest).collect(),
+		}
+	}
+	
+	/// Makes the rest pose the bind pose, so a mesh modelled around it is left as it is at rest
+	pub fn bind_rest_pose(&mut self) {
+		let globals = self.globals(&self.rest_pose());
+		for (joint, global) in self.joints.iter_mut().zip(globals) {
+			joint.inverse_bind = global.invert().unwrap_or(Matrix4::identity());
+		}
+	}
+	
+	/// The transform of every joint in the mesh's space
+	pub fn globals(&self, pose: &Pose) -> Vec<Matrix4<f32>> {
+		let mut globals: Vec<Matrix4<f32>> = Vec::with_capacity(self.joints.len());
+		for (i, joint) in self.joints.iter().enumerate() {
+			let local = pose.joints.get(i).unwrap_or(&joint.rest).matrix();
+			let global = match joint.parent {
+				Some(parent) => globals[parent] * local,
+				None => local,
+			};
+			globals.push(global);
+		}
+		globals
+	}
+	
+	/// The matrices the vertex shader blends, moving vertices from the bind pose to `pose`
+	pub fn skinning_matrices(&self, pose: &Pose) -> Vec<Matrix4<f32>> {
+		self.globals(pose).into_iter()
+			.zip(&self.joints)
+			.map(|(global, joint)| global * joint.inverse_bind)
+			.collect()
+	}
+}
+
+/// The local transform of every joint of a skeleton
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pose {
+	pub joints: Vec<Trs>,
+}
+
+impl Pose {
+	/// Interpolates every joint towards `other`
+	pub fn blend(&self, other: &Pose, t: f32) -> Pose {
+		Pose {
+			joints: self.joints.iter().zip(&other.joints).map(|(a, b)| a.lerp(b, t)).collect(),
+		}
+	}
+}
+
+/// The keyframes animating one joint. Missing properties keep the joint's rest value.
+#[derive(Clone, Debug)]
+pub struct Channel {
+	pub joint: usize,
+	pub translation: Option<Curve<Vec3>>,
+	pub rotation: Option<Curve<Quaternion<f32>>>,
+	pub scale: Option<Curve<Vec3>>,
+}
+
+/// Keyframes for the joints of a skeleton, played by an `Animator`. Properties of a scene are
+/// animated with `animation::Clip` instead.
+#[derive(Clone, Debug)]
+pub struct SkeletalClip {
+	pub name: String,
+	/// Length in seconds, the time of the last keyframe
+	pub duration: f32,
+	pub channels: Vec<Channel>,
+}
+
+impl SkeletalClip {
+	pub fn new(name: &str, channels: Vec<Channel>) -> Self {
+		let duration = channels.iter().map(|c| {
+			let t = c.translation.as_ref().map_or(0.0, |k| k.end());
+			let r = c.rotation.as_ref().map_or(0.0, |k| k.end());
+			let s = c.scale.as_ref().map_or(0.0, |k| k.end());
+			t.max(r).max(s)
+		}).fold(0.0, f32::max);
+		SkeletalClip {
+			name: name.to_string(),
+			duration,
+			channels,
+		}
+	}
+	
+	/// The pose at `time` seconds. Joints the clip doesn't animate stay at rest.
+	pub fn sample(&self, skeleton: &Skeleton, time: f32) -> Pose {
+		let mut pose = skeleton.rest_pose();
+		for channel in &self.channels {
+			let trs = match pose.joints.get_mut(channel.joint) {
+				Some(trs) => trs,
+				None => continue,
+			};
+			if let Some(v) = channel.translation.as_ref().and_then(|k| k.sample(time)) {
+				trs.translation = v;
+			}
+			if let Some(v) = channel.rotation.as_ref().and_then(|k| k.sample(time)) {
+				trs.rotation = v;
+			}
+			if let Some(v) = channel.scale.as_ref().and_then(|k| k.sample(time)) {
+				trs.scale = v;
+			}
+		}
+		pose
+	}
+}
+
+/// A clip being played by an `Animator`
+#[derive(Clone, Debug)]
+pub struct Layer {
+	pub clip: Arc<SkeletalClip>,
+	/// Position in the clip in seconds
+	pub time: f32,
+	/// Multiplier for how fast time passes in the clip
+	pub speed: f32,
+	/// How much the layer counts towards the blended pose, relative to the other layers
+	pub weight: f32,
+	pub looping: bool,
+	/// Weight to move towards and how much it changes per second
+	fade: Option<(f32, f32)>,
+}
+
+/// Plays any number of clips at once and blends them by weight
+#[derive(Clone, Debug, Default)]
+pub struct Animator {
+	pub layers: Vec<Layer>,
+}
+
+impl Animator {
+	pub fn new() -> Self {
+		Animator::default()
+	}
+	
+	/// Starts playing a looping clip from the beginning, returning the index of its layer
+	pub fn play(&mut self, clip: Arc<SkeletalClip>, weight: f32) -> usize {
+		self.layers.push(Layer {
+			clip,
+			time: 0.0,
+			speed: 1.0,
+			weight,
+			looping: true,
+			fade: None,
+		});
+		self.layers.len() - 1
+	}
+	
+	/// Changes a layer's weight linearly over `seconds`
+	pub fn fade(&mut self, layer: usize, weight: f32, seconds: f32) {
+		if let Some(layer) = self.layers.get_mut(layer) {
+			if seconds <= 0.0 {
+				layer.weight = weight;
+				layer.fade = None;
+			} else {
+				layer.fade = Some((weight, (weight - layer.weight).abs() / seconds));
+			}
+		}
+	}
+	
+	/// Fades every current layer out and `clip` in over `seconds`. Layers are removed once
+	/// they've faded out completely.
+	pub fn cross_fade(&mut self, clip: Arc<SkeletalClip>, seconds: f32) {
+		for i in 0..self.layers.len() {
+			self.fade(i, 0.0, seconds);
+		}
+		let layer = self.play(clip, 0.0);
+		self.fade(layer, 1.0, seconds);
+	}
+	
+	/// Moves every layer forward by `ms` milliseconds. Layers left without any weight are removed.
+	pub fn advance(&mut self, ms: f32) {
+		let seconds = ms / 1000.0;
+		for layer in &mut self.layers {
+			layer.time += seconds * layer.speed;
+			let duration = layer.clip.duration;
+			if layer.looping && duration > 0.0 {
+				layer.time %= duration;
+				if layer.time < 0.0 {
+					layer.time += duration;
+				}
+			} else {
+				layer.time = layer.time.max(0.0).min(duration);
+			}
+			
+			if let Some((target, rate)) = layer.fade {
+				let step = rate * seconds;
+				if (target - layer.weight).abs() <= step {
+					layer.weight = target;
+					layer.fade = None;
+				} else if target > layer.weight {
+					layer.weight += step;
+				} else {
+					layer.weight -= step;
+				}
+			}
+		}
+		self.layers.retain(|l| l.weight > 0.0 || l.fade.is_some());
+	}
+	
+	/// The weighted blend of every layer's current pose, or the rest pose if nothing is playing
+	pub fn pose(&self, skeleton: &Skeleton) -> Pose {
+		let mut blended: Option<Pose> = None;
+		let mut total = 0.0;
+		for layer in self.layers.iter().filter(|l| l.weight > 0.0) {
+			let pose = layer.clip.sample(skeleton, layer.time);
+			total += layer.weight;
+			blended = Some(match blended {
+				// Each layer takes its share of the weight seen so far
+				Some(b) => b.blend(&pose, layer.weight / total),
+				None => pose,
+			});
+		}
+		blended.unwrap_or_else(|| skeleton.rest_pose())
+	}
+}
+
+/// The skeleton and current pose of an object with a `SkinnedVertex` mesh
+#[derive(Clone, Debug)]
+pub struct Skin {
+	pub skeleton: Arc<Skeleton>,
+	pub pose: Pose,
+}
+
+impl Skin {
+	/// Starts out in the skeleton's rest pose
+	pub fn new(skeleton: Arc<Skeleton>) -> Self {
+		Skin {
+			pose: skeleton.rest_pose(),
+			skeleton,
+		}
+	}
+	
+	pub(crate) fn matrices(&self) -> Vec<Matrix4<f32>> {
+		self.skeleton.skinning_matrices(&self.pose)
+	}
+}
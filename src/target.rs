@@ -0,0 +1,135 @@
+//! Offscreen render targets: the scene drawn from another camera into an image that objects can
+//! use as a texture, for mirrors, monitors and thumbnails. A 2D overlay of one is a quad on its own
+//! layer, drawn by a last `View` with an orthographic camera that only clears depth. Targets that
+//! read back copy what they draw into memory the CPU can read, to save thumbnails.
+//!
+//! Only the color image outlives the frame. The depth buffer is transient, kept just for the
+//! render pass, so it can't be sampled or read back.
+
+use std::sync::Arc;
+
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBuffer, AutoCommandBufferBuilder};
+use vulkano::image::{AttachmentImage, ImageAccess, ImageUsage};
+use vulkano::format::Format;
+use vulkano::device::{DeviceOwned, Queue};
+use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, RenderPassAbstract};
+
+use td::{Camera, Color};
+use texture::Texture;
+use RenderInternal;
+
+/// When a render target is drawn
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Refresh {
+	EveryFrame,
+	/// Drawn in the next frame and then `Paused`, for pictures that don't change
+	Once,
+	/// Keeps what was last drawn
+	Paused,
+}
+
+pub struct RenderTarget {
+	/// Its aspect ratio is kept matching the target's
+	pub camera: Camera,
+	pub background: Color,
+	/// Only objects with a layer in this mask are drawn
+	pub layers: u32,
+	pub refresh: Refresh,
+	/// Copies each picture drawn into memory the CPU can read, for `pixels`. Only window formats
+	/// with four 8-bit channels can be read back.
+	pub read_back: bool,
+	color: Arc<AttachmentImage<Format>>,
+	/// Made the first time a picture is read back
+	readback: Option<Arc<CpuAccessibleBuffer<[u8]>>>,
+	texture: Arc<Texture>,
+	/// Made the first time the target is drawn, when the render pass and its depth format exist
+	framebuffer: Option<Arc<FramebufferAbstract + Send + Sync>>,
+	dimensions: [u32; 2],
+}
+
+impl RenderTarget {
+	/// A target `width` by `height` pixels big, drawn every frame from `camera`
+	pub fn new(internal: &RenderInternal, width: u32, height: u32, camera: Camera) -> Result<Self, ()> {
+		if width == 0 || height == 0 {
+			return Err(());
+		}
+		let dimensions = [width, height];
+		// The same format as the window, so the same pipelines can draw into both
+		let usage = ImageUsage { sampled: true, transfer_source: true, ..ImageUsage::none() };
+		let color = AttachmentImage::with_usage(internal.device.clone(), dimensions, internal.swapchain.format(), usage).map_err(|_| ())?;
+		let texture = Arc::new(Texture::from_image(&internal.device, color.clone(), dimensions));
+		
+		let mut target = RenderTarget {
+			camera,
+			background: Color::black(),
+			layers: !0,
+			refresh: Refresh::EveryFrame,
+			read_back: false,
+			color,
+			readback: None,
+			texture,
+			framebuffer: None,
+			dimensions,
+		};
+		target.camera.proj.set_aspect(width as f32 / height as f32);
+		Ok(target)
+	}
+	
+	/// What was drawn, to put in an object's material. Objects showing it aren't drawn into the
+	/// target itself.
+	pub fn texture(&self) -> Arc<Texture> {
+		self.texture.clone()
+	}
+	
+	pub fn dimensions(&self) -> [u32; 2] {
+		self.dimensions
+	}
+	
+	/// The last picture read back, as RGBA bytes row by row from the top left, or `None` if there
+	/// isn't one yet or the frame copying it hasn't finished
+	pub fn pixels(&self) -> Option<Vec<u8>> {
+		let contents = self.readback.as_ref()?.read().ok()?;
+		let mut pixels = contents.to_vec();
+		match self.color.format() {
+			Format::B8G8R8A8Unorm | Format::B8G8R8A8Srgb => for pixel in pixels.chunks_mut(4) {
+				pixel.swap(0, 2);
+			},
+			_ => {}
+		}
+		Some(pixels)
+	}
+	
+	/// The commands copying the picture into the readback buffer, to run after the frame drawing
+	/// it. Only window formats with four 8-bit channels can be read back.
+	pub(crate) fn readback(&mut self, queue: &Arc<Queue>) -> Result<AutoCommandBuffer, ()> {
+		match self.color.format() {
+			Format::B8G8R8A8Unorm | Format::B8G8R8A8Srgb | Format::R8G8B8A8Unorm | Format::R8G8B8A8Srgb => {},
+			_ => return Err(()),
+		}
+		if self.readback.is_none() {
+			let len = self.dimensions[0] as usize * self.dimensions[1] as usize * 4;
+			let buffer = CpuAccessibleBuffer::from_iter(queue.device().clone(), BufferUsage::transfer_destination(), (0..len).map(|_| 0u8))
+				.map_err(|_| ())?;
+			self.readback = Some(buffer);
+		}
+		AutoCommandBufferBuilder::primary_one_time_submit(queue.device().clone(), queue.family())
+			.map_err(|_| ())?
+			.copy_image_to_buffer(self.color.clone(), self.readback.clone().unwrap())
+			.map_err(|_| ())?
+			.build()
+			.map_err(|_| ())
+	}
+	
+	pub(crate) fn framebuffer(&mut self, render_pass: &Arc<RenderPassAbstract + Send + Sync>, depth_format: Format) -> Result<Arc<FramebufferAbstract + Send + Sync>, ()> {
+		if self.framebuffer.is_none() {
+			let depth = AttachmentImage::transient(render_pass.device().clone(), self.dimensions, depth_format).map_err(|_| ())?;
+			let framebuffer = Framebuffer::start(render_pass.clone())
+				.add(self.color.clone()).map_err(|_| ())?
+				.add(depth).map_err(|_| ())?
+				.build().map_err(|_| ())?;
+			self.framebuffer = Some(Arc::new(framebuffer));
+		}
+		Ok(self.framebuffer.clone().unwrap())
+	}
+}
@@ -1,7 +1,130 @@
-use cgmath::{Matrix4, Euler, Vector4, Vector3, Zero, Point3, Deg, InnerSpace, Angle, PerspectiveFov};
+use vulkano::pipeline::vertex::Vertex as VulkanoVertex;
+use cgmath::{Matrix3, Matrix4, Euler, Vector4, Vector3, Quaternion, Zero, One, Point3, Deg, InnerSpace, Angle, Rotation3, Matrix, SquareMatrix, PerspectiveFov, Ortho};
 
 pub type Vec3 = Vector3<f32>;
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingSphere {
+	pub center: Vec3,
+	pub radius: f32,
+}
+
+impl BoundingSphere {
+	/// A sphere around the center of the vertices' bounding box
+	pub fn from_verts<V: MeshVertex>(verts: &[V]) -> Self {
+		if verts.is_empty() {
+			return BoundingSphere { center: Vector3::zero(), radius: 0.0 };
+		}
+		let mut min = verts[0].position();
+		let mut max = min;
+		for v in verts {
+			let p = v.position();
+			min = Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+			max = Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+		}
+		let center = (min + max) / 2.0;
+		let radius = verts.iter().map(|v| (v.position() - center).magnitude2()).fold(0.0, f32::max).sqrt();
+		BoundingSphere { center, radius }
+	}
+	
+	/// The sphere containing this one after it has been transformed
+	pub fn transform(&self, m: &Matrix4<f32>) -> Self {
+		let center = (m * self.center.extend(1.0)).truncate();
+		let scale = m.x.truncate().magnitude().max(m.y.truncate().magnitude()).max(m.z.truncate().magnitude());
+		BoundingSphere { center, radius: self.radius * scale }
+	}
+}
+
+/// An axis aligned bounding box
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+	pub min: Vec3,
+	pub max: Vec3,
+}
+
+impl Aabb {
+	pub fn from_verts<V: MeshVertex>(verts: &[V]) -> Self {
+		let mut points = verts.iter().map(|v| v.position());
+		let first = points.next().unwrap_or(Vector3::zero());
+		points.fold(Aabb { min: first, max: first }, |b, p| Aabb {
+			min: Vector3::new(b.min.x.min(p.x), b.min.y.min(p.y), b.min.z.min(p.z)),
+			max: Vector3::new(b.max.x.max(p.x), b.max.y.max(p.y), b.max.z.max(p.z)),
+		})
+	}
+	
+	pub fn center(&self) -> Vec3 {
+		(self.min + self.max) / 2.0
+	}
+	
+	/// Half the size along each axis
+	pub fn extent(&self) -> Vec3 {
+		(self.max - self.min) / 2.0
+	}
+	
+	pub fn bounding_sphere(&self) -> BoundingSphere {
+		BoundingSphere { center: self.center(), radius: self.extent().magnitude() }
+	}
+}
+
+/// A half-line, such as the one under a point on the screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+	pub origin: Vec3,
+	/// Always normalized
+	pub direction: Vec3,
+}
+
+impl Ray {
+	pub fn at(&self, distance: f32) -> Vec3 {
+		self.origin + self.direction * distance
+	}
+}
+
+/// The points `p` where `normal.dot(p) + distance` is zero, with `normal` pointing to the side
+/// that counts as inside
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Plane {
+	pub normal: Vec3,
+	pub distance: f32,
+}
+
+impl Plane {
+	/// How far a point is from the plane, negative when it's outside
+	pub fn signed_distance(&self, p: Vec3) -> f32 {
+		self.normal.dot(p) + self.distance
+	}
+	
+	fn from_row(row: Vector4<f32>) -> Self {
+		let normal = row.truncate();
+		let length = normal.magnitude();
+		if length > 0.0 {
+			Plane { normal: normal / length, distance: row.w / length }
+		} else {
+			// The far plane of an infinite projection, which everything is inside of
+			Plane { normal, distance: 1.0 }
+		}
+	}
+}
+
+/// The space a camera can see, as six planes facing inwards
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frustum {
+	/// Left, right, bottom, top, near and far
+	pub planes: [Plane; 6],
+}
+
+impl Frustum {
+	pub fn contains_point(&self, p: Vec3) -> bool {
+		self.planes.iter().all(|plane| plane.signed_distance(p) >= 0.0)
+	}
+	
+	/// Whether any of a sphere might be visible. Spheres near the corners can pass while being
+	/// just outside.
+	pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+		self.planes.iter().all(|plane| plane.signed_distance(sphere.center) >= -sphere.radius)
+	}
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct Color {
 	r: f32,
@@ -26,12 +149,54 @@ impl Color {
 	pub fn black() -> Color { Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 } }
 }
 
+/// The point light the scene is lit by
+#[derive(Copy, Clone, Debug)]
+pub struct Light {
+	pub position: Vec3,
+	pub color: Color,
+	/// How much of the light's color reaches surfaces facing away from it
+	pub ambient: f32,
+}
+
+impl Default for Light {
+	fn default() -> Self {
+		Light {
+			position: Vector3::new(4.0, 3.0, 2.0),
+			color: Color::white(),
+			ambient: 0.1,
+		}
+	}
+}
+
+/// A vertex type meshes can be made of. The layout comes from `vulkano`'s `impl_vertex!`, and a
+/// pipeline for it has to be registered with `Renderer::add_vertex_format` before it's drawn.
+pub trait MeshVertex: VulkanoVertex + Copy {
+	/// Used for bounds and level of detail selection
+	fn position(&self) -> Vec3;
+	
+	/// How many joints a skeleton needs to move the vertex, one more than the highest joint it's
+	/// attached to. Zero for vertices that aren't skinned.
+	fn joint_count(&self) -> usize {
+		0
+	}
+}
+
+impl MeshVertex for Vertex {
+	fn position(&self) -> Vec3 {
+		self.vec3()
+	}
+}
+
 #[derive(Copy, Clone, Debug)]
 #[allow(non_snake_case)]
 pub struct Vertex {
 	pub a_Pos: [f32; 4],
 	pub a_Color: [f32; 4],
 	pub a_Normal: [f32; 3],
+	pub a_TexCoord: [f32; 2],
+	/// Tangent direction in `xyz` and the bitangent's handedness (1 or -1) in `w`. Left at zero
+	/// for meshes without tangents, which disables normal mapping for them.
+	pub a_Tangent: [f32; 4],
 }
 
 impl Vertex {
@@ -40,6 +205,8 @@ impl Vertex {
 			a_Pos: [x, y, z, w],
 			a_Color: color.raw(),
 			a_Normal: [0.0, 0.0, 0.0],
+			a_TexCoord: [0.0, 0.0],
+			a_Tangent: [0.0, 0.0, 0.0, 0.0],
 		}
 	}
 	
@@ -62,45 +229,167 @@ impl Vertex {
 	}
 }
 
-impl_vertex!(Vertex, a_Pos, a_Color, a_Normal);
+impl_vertex!(Vertex, a_Pos, a_Color, a_Normal, a_TexCoord, a_Tangent);
+
+/// How a camera maps what it sees onto the screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+	/// A `far` plane at infinity is allowed, which works best with `Renderer::set_reversed_z`
+	Perspective(PerspectiveFov<f32>),
+	/// Parallel projection of a view `height` units tall and as wide as the aspect ratio makes it,
+	/// centered on the camera
+	Orthographic {
+		height: f32,
+		aspect: f32,
+		near: f32,
+		far: f32,
+	},
+	/// Parallel projection of a fixed box around the camera, which is stretched to fill the
+	/// viewport whatever its aspect ratio
+	OrthographicBounds(Ortho<f32>),
+	/// Any projection matrix, in the same clip space as the others
+	Custom(Matrix4<f32>),
+}
+
+impl Projection {
+	pub fn matrix(&self) -> Matrix4<f32> {
+		match *self {
+			Projection::Perspective(p) if p.far.is_infinite() => {
+				let f = 1.0 / (p.fovy / 2.0).tan();
+				Matrix4::new(
+					f / p.aspect, 0.0, 0.0, 0.0,
+					0.0, f, 0.0, 0.0,
+					0.0, 0.0, -1.0, -1.0,
+					0.0, 0.0, -2.0 * p.near, 0.0,
+				)
+			},
+			Projection::Perspective(p) => p.into(),
+			Projection::Orthographic { height, aspect, near, far } => {
+				let (w, h) = (height * aspect / 2.0, height / 2.0);
+				Ortho { left: -w, right: w, bottom: -h, top: h, near, far }.into()
+			},
+			Projection::OrthographicBounds(o) => o.into(),
+			Projection::Custom(m) => m,
+		}
+	}
+	
+	/// The width of the view divided by its height, for the projections that have one
+	pub fn aspect(&self) -> Option<f32> {
+		match *self {
+			Projection::Perspective(p) => Some(p.aspect),
+			Projection::Orthographic { aspect, .. } => Some(aspect),
+			_ => None,
+		}
+	}
+	
+	/// Fits the projection to a viewport of a new shape. Fixed bounds and custom matrices are left
+	/// as they are.
+	pub fn set_aspect(&mut self, new: f32) {
+		match *self {
+			Projection::Perspective(ref mut p) => p.aspect = new,
+			Projection::Orthographic { ref mut aspect, .. } => *aspect = new,
+			_ => {},
+		}
+	}
+	
+	pub fn is_orthographic(&self) -> bool {
+		self.matrix().w.w != 0.0
+	}
+}
+
+impl From<PerspectiveFov<f32>> for Projection {
+	fn from(p: PerspectiveFov<f32>) -> Self {
+		Projection::Perspective(p)
+	}
+}
+
+impl From<Ortho<f32>> for Projection {
+	fn from(o: Ortho<f32>) -> Self {
+		Projection::OrthographicBounds(o)
+	}
+}
+
+/// The direction that yaw 0 faces and the direction yaw 90 faces, both level with `up`
+fn yaw_basis(up: Vec3) -> (Vec3, Vec3) {
+	let x: Vec3 = Vector3::unit_x();
+	let reference = x - up * up.dot(x);
+	let reference = if reference.magnitude2() > 1e-6 {
+		reference.normalize()
+	} else {
+		let z: Vec3 = Vector3::unit_z();
+		(z - up * up.dot(z)).normalize()
+	};
+	(reference, reference.cross(up))
+}
+
+/// Where a camera with this pitch and yaw in degrees looks, for a world whose up is `up`
+pub(crate) fn euler_direction(up: Vec3, pitch: f32, yaw: f32) -> Vec3 {
+	let (reference, side) = yaw_basis(up);
+	let (pitch, yaw) = (pitch.to_radians(), yaw.to_radians());
+	((reference * yaw.cos() + side * yaw.sin()) * pitch.cos() + up * pitch.sin()).normalize()
+}
+
+/// The pitch and yaw in degrees that look in direction `front`, the inverse of `euler_direction`
+pub(crate) fn direction_euler(up: Vec3, front: Vec3) -> (f32, f32) {
+	let (reference, side) = yaw_basis(up);
+	let pitch = front.dot(up).max(-1.0).min(1.0).asin();
+	let yaw = front.dot(side).atan2(front.dot(reference));
+	(pitch.to_degrees(), yaw.to_degrees())
+}
+
+/// The top of a camera with this pitch and yaw but no roll, which still depends on the yaw when
+/// looking straight up or down
+fn unrolled_up(up: Vec3, pitch: f32, yaw: f32) -> Vec3 {
+	let right = euler_direction(up, 0.0, yaw).cross(up).normalize();
+	right.cross(euler_direction(up, pitch, yaw)).normalize()
+}
+
+/// A rotation that turns the camera's local axes, looking down -z with y up, to face `front`
+/// with its top towards `up`
+fn facing(front: Vec3, up: Vec3) -> Quaternion<f32> {
+	let right = front.cross(up).normalize();
+	let up = right.cross(front);
+	Quaternion::from(Matrix3::from_cols(right, up, -front)).normalize()
+}
 
 pub struct Camera {
 	pos: Vec3,
-	rot: Euler<Deg<f32>>,
-	pub proj: PerspectiveFov<f32>,
+	orientation: Quaternion<f32>,
+	world_up: Vec3,
+	pub proj: Projection,
 	front: Vec3,
 	right: Vec3,
 	up: Vec3,
 }
 
 impl Camera {
-	pub fn new(proj: PerspectiveFov<f32>) -> Camera {
-		let worldup: Vec3 = Vector3::new(0.0, 1.0, 0.0);
-		let rot = Euler::new(Deg(0.0), Deg(0.0), Deg(0.0));
-		let front: Vec3 = Vector3::new(
-			(rot.y * rot.x.cos()).cos(),
-			rot.x.sin(),
-			(rot.y * rot.x.cos()).sin()
-		).normalize();
-		let right = (front.cross(worldup)).normalize();
-		let up = (right.cross(front)).normalize();
-		
-		Camera {
+	/// A camera at the origin facing along positive x, with positive y up
+	pub fn new<P: Into<Projection>>(proj: P) -> Camera {
+		let mut camera = Camera {
 			pos: Vector3::zero(),
-			rot,
-			proj,
-			front,
-			right,
-			up,
-		}
+			orientation: Quaternion::one(),
+			world_up: Vector3::unit_y(),
+			proj: proj.into(),
+			front: Vector3::zero(),
+			right: Vector3::zero(),
+			up: Vector3::zero(),
+		};
+		camera.set_rot(Euler::new(Deg(0.0), Deg(0.0), Deg(0.0)));
+		camera
 	}
 	
 	pub fn get_pos(&self) -> Vec3 {
 		self.pos
 	}
 	
+	/// Pitch, yaw and roll in `x`, `y` and `z`. Yaw turns around the world's up axis and pitch
+	/// tilts towards it, then roll turns clockwise around the direction the camera faces.
 	pub fn get_rot(&self) -> Euler<Deg<f32>> {
-		self.rot
+		let (pitch, yaw) = direction_euler(self.world_up, self.front);
+		// Roll is how far the camera's top is turned from where it would be without any
+		let unrolled = unrolled_up(self.world_up, pitch, yaw);
+		let roll = unrolled.cross(self.up).dot(self.front).atan2(unrolled.dot(self.up));
+		Euler::new(Deg(pitch), Deg(yaw), Deg(roll.to_degrees()))
 	}
 	
 	pub fn set_pos(&mut self, pos: Vec3) {
@@ -109,23 +398,168 @@ impl Camera {
 	}
 	
 	pub fn set_rot(&mut self, rot: Euler<Deg<f32>>) {
-		self.rot = rot;
+		let front = euler_direction(self.world_up, rot.x.0, rot.y.0);
+		let base = facing(front, unrolled_up(self.world_up, rot.x.0, rot.y.0));
+		self.orientation = Quaternion::from_axis_angle(front, rot.z) * base;
+		self.update();
+	}
+	
+	/// Rotation from the camera's own axes, where it looks down negative z with y up, to the world
+	pub fn orientation(&self) -> Quaternion<f32> {
+		self.orientation
+	}
+	
+	pub fn set_orientation(&mut self, orientation: Quaternion<f32>) {
+		self.orientation = orientation.normalize();
+		self.update();
+	}
+	
+	/// Turns to face `target` without any roll
+	pub fn look_at(&mut self, target: Vec3) {
+		let front = target - self.pos;
+		if front.magnitude2() == 0.0 {
+			return;
+		}
+		let front = front.normalize();
+		// Straight up or down there's no level direction, so keep the camera's top where it was
+		let up = if front.cross(self.world_up).magnitude2() > 1e-8 { self.world_up } else { self.up };
+		self.orientation = facing(front, up);
 		self.update();
 	}
 	
+	/// Turns the camera around an axis in world space, keeping its position
+	pub fn rotate_around_axis(&mut self, axis: Vec3, angle: Deg<f32>) {
+		if axis.magnitude2() == 0.0 {
+			return;
+		}
+		self.orientation = (Quaternion::from_axis_angle(axis.normalize(), angle) * self.orientation).normalize();
+		self.update();
+	}
+	
+	/// Turns the camera clockwise around the direction it faces
+	pub fn roll(&mut self, angle: Deg<f32>) {
+		let front = self.front;
+		self.rotate_around_axis(front, angle);
+	}
+	
+	/// The direction pitch and yaw are measured against, positive y unless changed
+	pub fn world_up(&self) -> Vec3 {
+		self.world_up
+	}
+	
+	/// Changes which way is up, for example to positive z for data from CAD tools. The camera keeps
+	/// facing the same way, only its pitch, yaw and roll change.
+	pub fn set_world_up(&mut self, up: Vec3) {
+		if up.magnitude2() > 0.0 {
+			self.world_up = up.normalize();
+		}
+	}
+	
 	pub fn get_vec(&self) -> (Vec3, Vec3, Vec3) {
 		(self.front, self.right, self.up)
 	}
 	
 	fn update(&mut self) {
-		let worldup: Vec3 = Vector3::new(0.0, 1.0, 0.0);
-		self.front = Vector3::new(
-			self.rot.y.cos() * self.rot.x.cos(),
-			self.rot.x.sin(),
-			self.rot.y.sin() * self.rot.x.cos()
-		).normalize();
-		self.right = (self.front.cross(worldup)).normalize();
-		self.up = (self.right.cross(self.front)).normalize();
+		self.front = (self.orientation * -Vec3::unit_z()).normalize();
+		self.right = (self.orientation * Vec3::unit_x()).normalize();
+		self.up = (self.orientation * Vec3::unit_y()).normalize();
+	}
+	
+	/// The projection and the view together, taking world space to clip space
+	pub fn view_proj(&self) -> Matrix4<f32> {
+		self.proj.matrix() * self.get_view()
+	}
+	
+	/// Where a point is drawn in a viewport `size` pixels large, in pixels from the viewport's
+	/// corner at its origin, the way the renderer maps clip space. `z` is its depth, always from 0
+	/// at the near plane to 1 at the far plane, even when the renderer uses reversed depth. Points
+	/// behind the camera have no position.
+	pub fn world_to_screen(&self, point: Vec3, size: [f32; 2]) -> Option<Vec3> {
+		let clip = self.view_proj() * point.extend(1.0);
+		if clip.w <= 0.0 {
+			return None;
+		}
+		let ndc = clip.truncate() / clip.w;
+		Some(Vector3::new((ndc.x + 1.0) / 2.0 * size[0], (ndc.y + 1.0) / 2.0 * size[1], (ndc.z + 1.0) / 2.0))
+	}
+	
+	/// The inverse of `world_to_screen`: the point in the world drawn at a pixel with a depth. Like
+	/// there, depth is 0 at the near plane, so values read from a reversed depth buffer have to be
+	/// flipped with `1.0 - depth` first.
+	pub fn unproject(&self, x: f32, y: f32, depth: f32, size: [f32; 2]) -> Option<Vec3> {
+		let inverse = self.view_proj().invert()?;
+		let ndc = Vector4::new(x / size[0] * 2.0 - 1.0, y / size[1] * 2.0 - 1.0, depth * 2.0 - 1.0, 1.0);
+		let p = inverse * ndc;
+		if p.w == 0.0 {
+			return None;
+		}
+		Some(p.truncate() / p.w)
+	}
+	
+	/// The ray from the near plane through everything drawn at a pixel, for picking
+	pub fn screen_to_world_ray(&self, x: f32, y: f32, size: [f32; 2]) -> Option<Ray> {
+		// Halfway in depth still works with the far plane at infinity
+		let near = self.unproject(x, y, 0.0, size)?;
+		let further = self.unproject(x, y, 0.5, size)?;
+		let direction = further - near;
+		if direction.magnitude2() == 0.0 {
+			return None;
+		}
+		Some(Ray { origin: near, direction: direction.normalize() })
+	}
+	
+	/// The planes around what the camera sees, in world space
+	pub fn frustum(&self) -> Frustum {
+		let m = self.view_proj();
+		let (x, y, z, w) = (m.row(0), m.row(1), m.row(2), m.row(3));
+		Frustum {
+			planes: [
+				Plane::from_row(w + x),
+				Plane::from_row(w - x),
+				Plane::from_row(w + y),
+				Plane::from_row(w - y),
+				Plane::from_row(w + z),
+				Plane::from_row(w - z),
+			],
+		}
+	}
+	
+	/// Moves the camera straight back or forward, keeping its orientation, until the box fits in
+	/// view. Orthographic projections by height are zoomed to fit it instead, and the clip planes
+	/// are left alone, so the far plane might still cut off a large box.
+	pub fn frame_bounds(&mut self, bounds: &Aabb) {
+		let (center, extent) = (bounds.center(), bounds.extent());
+		// Half of how much space the box takes up along one of the camera's axes
+		let across = |axis: Vec3| extent.x * axis.x.abs() + extent.y * axis.y.abs() + extent.z * axis.z.abs();
+		let (half_width, half_height, half_depth) = (across(self.right), across(self.up), across(self.front));
+		let distance = match self.proj {
+			Projection::Perspective(p) => {
+				let half_y = p.fovy.0 / 2.0;
+				let half_x = (half_y.tan() * p.aspect).atan();
+				extent.magnitude() / half_y.min(half_x).sin()
+			},
+			Projection::Orthographic { ref mut height, aspect, near, .. } => {
+				*height = (half_height * 2.0).max(half_width * 2.0 / aspect);
+				(half_depth + near) * 1.01
+			},
+			Projection::OrthographicBounds(o) => (half_depth + o.near) * 1.01,
+			Projection::Custom(_) => extent.magnitude(),
+		};
+		self.pos = center - self.front * distance;
+	}
+	
+	/// Roughly how much of the viewport's height a sphere covers, where 1.0 is all of it
+	pub fn projected_size(&self, sphere: &BoundingSphere) -> f32 {
+		// How much of half the view's height one unit covers, at one unit away for perspective
+		let scale = self.proj.matrix().y.y.abs();
+		if self.proj.is_orthographic() {
+			return sphere.radius * scale;
+		}
+		let distance = (sphere.center - self.pos).magnitude();
+		if distance <= sphere.radius {
+			return ::std::f32::INFINITY;
+		}
+		sphere.radius * scale / distance
 	}
 	
 	pub fn get_view(&self) -> Matrix4<f32> {
@@ -1,8 +1,7 @@
 use std;
 use std::collections::HashMap;
 
-use {App, Renderer};
-use render::{Render};
+use {App, Renderer, Context};
 use obj::{Mesh, Object};
 use td::*;
 use math::{Zero, Vector3, Rad, PerspectiveFov, Deg, Euler, InnerSpace};
@@ -12,14 +11,14 @@ mod data {
 	use td::Vertex;
 	pub static DATA: ([Vertex; 8], [u32; 36]) = (
 		[
-			Vertex { a_Pos: [-0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0] },
-			Vertex { a_Pos: [0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0] },
-			Vertex { a_Pos: [0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0] },
-			Vertex { a_Pos: [-0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0] },
-			Vertex { a_Pos: [-0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0] },
-			Vertex { a_Pos: [0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0] },
-			Vertex { a_Pos: [0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0] },
-			Vertex { a_Pos: [-0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0] },
+			Vertex { a_Pos: [-0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+			Vertex { a_Pos: [0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+			Vertex { a_Pos: [0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+			Vertex { a_Pos: [-0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+			Vertex { a_Pos: [-0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+			Vertex { a_Pos: [0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+			Vertex { a_Pos: [0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+			Vertex { a_Pos: [-0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.0, 1.0, 1.0], a_Normal: [0.0, 0.0, -1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
 		],
 		[
 			0, 1, 2, 2, 3, 0, // top
@@ -32,51 +31,52 @@ mod data {
 	);
 	
 	pub static VERTEX_DATA: [Vertex; 36] = [
-		Vertex { a_Pos: [-0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, -1.0] },
-		Vertex { a_Pos: [0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, -1.0] },
-		Vertex { a_Pos: [0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, -1.0] },
-		Vertex { a_Pos: [0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, -1.0] },
-		Vertex { a_Pos: [-0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, -1.0] },
-		Vertex { a_Pos: [-0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, -1.0] },
+		Vertex { a_Pos: [-0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, -1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, -1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, -1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, -1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [-0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, -1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [-0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, -1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
 		
-		Vertex { a_Pos: [-0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, 1.0] },
-		Vertex { a_Pos: [0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, 1.0] },
-		Vertex { a_Pos: [0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, 1.0] },
-		Vertex { a_Pos: [0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, 1.0] },
-		Vertex { a_Pos: [-0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, 1.0] },
-		Vertex { a_Pos: [-0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, 1.0] },
+		Vertex { a_Pos: [-0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, 1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, 1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, 1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, 1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [-0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, 1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [-0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 0.0, 1.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
 		
-		Vertex { a_Pos: [-0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [-1.0, 0.0, 0.0] },
-		Vertex { a_Pos: [-0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [-1.0, 0.0, 0.0] },
-		Vertex { a_Pos: [-0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [-1.0, 0.0, 0.0] },
-		Vertex { a_Pos: [-0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [-1.0, 0.0, 0.0] },
-		Vertex { a_Pos: [-0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [-1.0, 0.0, 0.0] },
-		Vertex { a_Pos: [-0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [-1.0, 0.0, 0.0] },
+		Vertex { a_Pos: [-0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [-1.0, 0.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [-0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [-1.0, 0.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [-0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [-1.0, 0.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [-0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [-1.0, 0.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [-0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [-1.0, 0.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [-0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [-1.0, 0.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
 		
-		Vertex { a_Pos: [0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [1.0, 0.0, 0.0] },
-		Vertex { a_Pos: [0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [1.0, 0.0, 0.0] },
-		Vertex { a_Pos: [0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [1.0, 0.0, 0.0] },
-		Vertex { a_Pos: [0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [1.0, 0.0, 0.0] },
-		Vertex { a_Pos: [0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [1.0, 0.0, 0.0] },
-		Vertex { a_Pos: [0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [1.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [1.0, 0.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [1.0, 0.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [1.0, 0.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [1.0, 0.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [1.0, 0.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [1.0, 0.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
 		
-		Vertex { a_Pos: [-0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, -1.0, 0.0] },
-		Vertex { a_Pos: [0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, -1.0, 0.0] },
-		Vertex { a_Pos: [0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, -1.0, 0.0] },
-		Vertex { a_Pos: [0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, -1.0, 0.0] },
-		Vertex { a_Pos: [-0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, -1.0, 0.0] },
-		Vertex { a_Pos: [-0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, -1.0, 0.0] },
+		Vertex { a_Pos: [-0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, -1.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, -1.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, -1.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, -1.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [-0.5, -0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, -1.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [-0.5, -0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, -1.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
 		
-		Vertex { a_Pos: [-0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 1.0, 0.0] },
-		Vertex { a_Pos: [0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 1.0, 0.0] },
-		Vertex { a_Pos: [0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 1.0, 0.0] },
-		Vertex { a_Pos: [0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 1.0, 0.0] },
-		Vertex { a_Pos: [-0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 1.0, 0.0] },
-		Vertex { a_Pos: [-0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 1.0, 0.0] },
+		Vertex { a_Pos: [-0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 1.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 1.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 1.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 1.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [-0.5, 0.5, 0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 1.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
+		Vertex { a_Pos: [-0.5, 0.5, -0.5, 1.0], a_Color: [0.0, 0.8, 0.9, 1.0], a_Normal: [0.0, 1.0, 0.0], a_TexCoord: [0.0, 0.0], a_Tangent: [0.0, 0.0, 0.0, 0.0] },
 	];
 }
 
 #[test]
+#[ignore] // Opens a window and runs until Escape is pressed
 fn basic() {
 	struct Player {
 		pub camera: Camera,
@@ -124,7 +124,7 @@ fn basic() {
 			&mut self.player.camera
 		}
 		
-		fn handle_event(&mut self, event: Event, objects: &mut HashMap<String, Object>) {
+		fn handle_event(&mut self, event: Event, _context: Context) {
 			use window::VirtualKeyCode::*;
 			match event {
 				Event::WindowEvent { event, .. } => match event {
@@ -181,7 +181,7 @@ fn basic() {
 			}
 		}
 		
-		fn update(&mut self, ms: f32, objects: &mut HashMap<String, Object>) {
+		fn update(&mut self, ms: f32, _context: Context) {
 			let mut movement: Vec3 = Vec3::zero();
 			if (self.movement.0).0 { movement.x -= 1.0 };
 			if (self.movement.0).1 { movement.x += 1.0 };
@@ -197,10 +197,10 @@ fn basic() {
 			self.running
 		}
 		
-		fn start(&mut self, objects: &mut HashMap<String, Object>) {
-			let mesh = Mesh::new_pure(data::VERTEX_DATA.to_vec());
+		fn start(&mut self, context: Context) {
+			let mesh = Mesh::new_pure(context.internal, data::VERTEX_DATA.to_vec());
 			let obj = Object::from_mesh(mesh);
-			objects.insert("cube".to_string(), obj);
+			context.targets.insert("cube".to_string(), obj);
 		}
 	}
 	
@@ -227,4 +227,672 @@ fn basic() {
 	};
 	let mut renderer = Renderer::new(app);
 	renderer.run();
-}
\ No newline at end of file
+}
+
+#[test]
+fn wavefront_quad() {
+	use std::io::Cursor;
+	use format::wavefront;
+	
+	let src = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1 4//1\n";
+	let meshes = wavefront::parse(Cursor::new(src), None).unwrap();
+	assert_eq!(meshes.len(), 1);
+	assert_eq!(meshes[0].data.verts.len(), 4);
+	assert_eq!(meshes[0].data.indices, vec![0, 1, 2, 0, 2, 3]);
+	
+	match wavefront::parse(Cursor::new("v 0 0 0\nf 1 2 3\n"), None) {
+		Err(::format::LoadError::Parse { line, .. }) => assert_eq!(line, 2),
+		_ => panic!("expected a parse error"),
+	}
+}
+
+#[test]
+fn ply_ascii_quad() {
+	use format::{ply, LoadOptions};
+	
+	let src = "ply\nformat ascii 1.0\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+	           property uchar red\nproperty uchar green\nproperty uchar blue\n\
+	           element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+	           0 0 0 255 0 0\n1 0 0 255 0 0\n1 1 0 255 0 0\n0 1 0 255 0 0\n4 0 1 2 3\n";
+	let data = ply::parse(src.as_bytes(), &LoadOptions { generate_normals: true, ..Default::default() }).unwrap();
+	assert_eq!(data.verts.len(), 4);
+	assert_eq!(data.indices, vec![0, 1, 2, 0, 2, 3]);
+	assert_eq!(data.verts[0].a_Color, [1.0, 0.0, 0.0, 1.0]);
+	assert_eq!(data.verts[0].a_Normal, [0.0, 0.0, 1.0]);
+	
+	let negative = src.replace("4 0 1 2 3", "3 0 -1 2");
+	match ply::parse(negative.as_bytes(), &LoadOptions::default()) {
+		Err(::format::LoadError::Corrupt(_)) => {},
+		_ => panic!("expected negative indices to be rejected"),
+	}
+	
+	// A huge count in the header runs out of body instead of memory
+	let huge = src.replace("element vertex 4", "element vertex 4000000000").replace("element face 1", "element face 4000000000");
+	assert!(ply::parse(huge.as_bytes(), &LoadOptions::default()).is_err());
+	let binary = b"ply\nformat binary_little_endian 1.0\nelement vertex 4000000000\nproperty float x\nend_header\n";
+	assert!(ply::parse(binary, &LoadOptions::default()).is_err());
+}
+
+#[test]
+fn stl_binary_padding() {
+	use format::{stl, LoadOptions};
+	
+	// One facet followed by padding, with a header that starts like an ASCII file
+	let mut bytes = b"solid padded".to_vec();
+	bytes.resize(80, 0);
+	bytes.extend_from_slice(&1u32.to_le_bytes());
+	for f in &[0.0f32, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+		bytes.extend_from_slice(&f.to_le_bytes());
+	}
+	bytes.extend_from_slice(&[0; 2 + 16]);
+	let data = stl::parse(&bytes, &LoadOptions::default()).unwrap();
+	assert_eq!(data.verts.len(), 3);
+	assert_eq!(data.verts[1].a_Pos, [1.0, 0.0, 0.0, 1.0]);
+}
+
+/// Reads a `.glb` held in memory without uploading anything
+fn read_glb(bytes: &[u8]) -> ::format::gltf::Contents {
+	let ::gltf::Gltf { document, blob } = ::gltf::Gltf::from_slice(bytes).unwrap();
+	let buffers = blob.into_iter().map(::gltf::buffer::Data).collect::<Vec<_>>();
+	::format::gltf::read(&document, &buffers).unwrap()
+}
+
+#[test]
+fn gltf_import() {
+	use math::Vector4;
+	
+	// A triangle scaled by its node inside a translated parent, and an orthographic camera
+	let mut json = concat!(
+		r#"{"asset":{"version":"2.0"},"scene":0,"scenes":[{"nodes":[0,2]}],"#,
+		r#""nodes":[{"children":[1],"translation":[1,2,3]},{"name":"tri","mesh":0,"scale":[2,2,2]},"#,
+		r#"{"name":"cam","camera":0,"translation":[0,0,5]}],"#,
+		r#""meshes":[{"primitives":[{"attributes":{"POSITION":0},"material":0}]}],"#,
+		r#""materials":[{"pbrMetallicRoughness":{"baseColorFactor":[1,0,0,0.5]}}],"#,
+		r#""cameras":[{"type":"orthographic","orthographic":{"xmag":2,"ymag":1,"znear":0.1,"zfar":10}}],"#,
+		r#""accessors":[{"bufferView":0,"componentType":5126,"count":3,"type":"VEC3","min":[0,0,0],"max":[1,1,0]}],"#,
+		r#""bufferViews":[{"buffer":0,"byteLength":36}],"buffers":[{"byteLength":36}]}"#).to_string();
+	while json.len() % 4 != 0 {
+		json.push(' ');
+	}
+	let mut bin = Vec::new();
+	for f in &[0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+		bin.extend_from_slice(&f.to_le_bytes());
+	}
+	let mut bytes = b"glTF".to_vec();
+	bytes.extend_from_slice(&2u32.to_le_bytes());
+	bytes.extend_from_slice(&((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+	bytes.extend_from_slice(&(json.len() as u32).to_le_bytes());
+	bytes.extend_from_slice(b"JSON");
+	bytes.extend_from_slice(json.as_bytes());
+	bytes.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+	bytes.extend_from_slice(b"BIN\0");
+	bytes.extend_from_slice(&bin);
+	
+	let contents = read_glb(&bytes);
+	assert_eq!(contents.primitives.len(), 1);
+	let tri = &contents.primitives[0];
+	assert_eq!(tri.name, "tri");
+	assert_eq!(tri.data.indices, vec![0, 1, 2]);
+	let corner = tri.transform * Vector4::new(1.0, 0.0, 0.0, 1.0);
+	assert!((corner.truncate() - Vector3::new(3.0, 2.0, 3.0)).magnitude() < 1e-5);
+	assert_eq!(tri.color.raw(), [1.0, 0.0, 0.0, 0.5]);
+	
+	let (ref name, ref camera) = contents.cameras[0];
+	assert_eq!(name, "cam");
+	assert!((camera.get_pos() - Vector3::new(0.0, 0.0, 5.0)).magnitude() < 1e-5);
+	match camera.proj {
+		Projection::OrthographicBounds(o) => assert_eq!((o.left, o.right, o.bottom, o.top), (-2.0, 2.0, -1.0, 1.0)),
+		p => panic!("{:?}", p),
+	}
+}
+
+#[test]
+fn export_round_trip() {
+	use std::io::Cursor;
+	use format::{wavefront, ply, gltf, LoadOptions};
+	use obj::Topology;
+	
+	let mut data = ::obj::MeshData::new(data::DATA.0.to_vec(), data::DATA.1.to_vec());
+	data.verts[2].a_Color = [1.0, 0.6, 0.2, 0.4];
+	
+	let mut text = Vec::new();
+	wavefront::write(&mut text, &data).unwrap();
+	let meshes = wavefront::parse(Cursor::new(text), None).unwrap();
+	assert_eq!(meshes[0].data.indices, data.indices);
+	assert_eq!(meshes[0].data.verts[2].a_Color, data.verts[2].a_Color);
+	
+	let mut bytes = Vec::new();
+	ply::write(&mut bytes, &data).unwrap();
+	let read = ply::parse(&bytes, &LoadOptions::default()).unwrap();
+	assert_eq!(read.indices, data.indices);
+	assert_eq!(read.verts[3].a_Pos, data.verts[3].a_Pos);
+	
+	let mut bytes = Vec::new();
+	gltf::write_glb(&mut bytes, &data).unwrap();
+	let contents = read_glb(&bytes);
+	let read = &contents.primitives[0];
+	assert_eq!(read.topology, Topology::TriangleList);
+	assert_eq!(read.data.indices, data.indices);
+	assert_eq!(read.data.verts[2].a_Color, data.verts[2].a_Color);
+	assert_eq!(read.data.verts[3].a_Pos, data.verts[3].a_Pos);
+	
+	// Data without indices is a triangle list in vertex order
+	let data = ::obj::MeshData::new(data::VERTEX_DATA.to_vec(), Vec::new());
+	let mut text = Vec::new();
+	wavefront::write(&mut text, &data).unwrap();
+	assert_eq!(wavefront::parse(Cursor::new(text), None).unwrap()[0].data.indices.len(), 36);
+	let mut bytes = Vec::new();
+	ply::write(&mut bytes, &data).unwrap();
+	assert_eq!(ply::parse(&bytes, &LoadOptions::default()).unwrap().indices, (0..36).collect::<Vec<_>>());
+	let mut bytes = Vec::new();
+	gltf::write_glb(&mut bytes, &data).unwrap();
+	let contents = read_glb(&bytes);
+	assert_eq!(contents.primitives[0].topology, Topology::TriangleList);
+	assert_eq!(contents.primitives[0].data.indices, (0..36).collect::<Vec<_>>());
+}
+
+#[test]
+fn creased_normals() {
+	use geometry::{generate_normals, NormalMode, Weighting};
+	
+	// Every face of the cube meets its neighbours at 90 degrees, so a 60 degree crease splits
+	// each corner into one vertex per face
+	let mut data = ::obj::MeshData::new(data::VERTEX_DATA.to_vec(), (0..36).collect());
+	generate_normals(&mut data, NormalMode::Smooth { weighting: Weighting::Angle, crease: Some(Deg(60.0)) });
+	assert_eq!(data.verts.len(), 24);
+	for v in &data.verts {
+		assert!(Vec3::from(v.a_Normal).magnitude() > 0.99);
+	}
+	
+	generate_normals(&mut data, NormalMode::Smooth { weighting: Weighting::Angle, crease: None });
+	assert_eq!(data.verts.len(), 8);
+	
+	// A corner at -0.0 is the same position as one at 0.0
+	let white = Color::white();
+	let verts = vec![
+		Vertex::new(0.0, 0.0, 0.0, 1.0, white), Vertex::new(1.0, 0.0, 0.0, 1.0, white), Vertex::new(0.0, 1.0, 0.0, 1.0, white),
+		Vertex::new(-0.0, 0.0, 0.0, 1.0, white), Vertex::new(0.0, 1.0, 0.0, 1.0, white), Vertex::new(0.0, 0.0, 1.0, 1.0, white),
+	];
+	let mut data = ::obj::MeshData::new(verts, (0..6).collect());
+	generate_normals(&mut data, NormalMode::Smooth { weighting: Weighting::Area, crease: None });
+	assert_eq!(data.verts.len(), 4);
+}
+
+#[test]
+fn mirrored_tangents() {
+	use geometry::generate_tangents;
+	
+	// Two quads side by side, the right one with its texture mirrored across the shared edge
+	let verts = [(0.0, 0.0, 0.0), (1.0, 0.0, 1.0), (2.0, 0.0, 0.0), (0.0, 1.0, 0.0), (1.0, 1.0, 1.0), (2.0, 1.0, 0.0)].iter().map(|&(x, y, u)| {
+		let mut v = Vertex::new(x, y, 0.0, 1.0, Color::white());
+		v.a_Normal = [0.0, 0.0, 1.0];
+		v.a_TexCoord = [u, 1.0 - y];
+		v
+	}).collect();
+	let mut data = ::obj::MeshData::new(verts, vec![0, 1, 4, 0, 4, 3, 1, 2, 5, 1, 5, 4]);
+	generate_tangents(&mut data);
+	
+	// The vertices on the shared edge are split between the two sides
+	assert_eq!(data.verts.len(), 8);
+	let (left, right) = (data.verts[data.indices[0] as usize].a_Tangent, data.verts[data.indices[7] as usize].a_Tangent);
+	assert_eq!(&left[..3], &[1.0, 0.0, 0.0]);
+	assert_eq!(&right[..3], &[-1.0, 0.0, 0.0]);
+	assert_eq!(left[3], -right[3]);
+}
+
+#[test]
+fn shapes_face_outwards() {
+	use shapes;
+	
+	let all = vec![
+		shapes::cuboid(Vec3::new(1.0, 2.0, 3.0), Color::white()),
+		shapes::grid(2.0, 2.0, 3, 4, Color::white()),
+		shapes::uv_sphere(1.0, 16, 8, Color::white()),
+		shapes::icosphere(1.0, 2, Color::white()),
+		shapes::cylinder(1.0, 2.0, 12, Color::white()),
+		shapes::cone(1.0, 2.0, 12, Color::white()),
+		shapes::capsule(0.5, 2.0, 12, 4, Color::white()),
+		shapes::torus(1.0, 0.25, 16, 8, Color::white()),
+		shapes::arrow(1.0, 0.05, 0.1, 0.2, 12, Color::white()),
+	];
+	
+	// The winding of every triangle should agree with the normals of its vertices
+	for data in &all {
+		assert_eq!(data.indices.len() % 3, 0);
+		for tri in data.indices.chunks(3) {
+			let v = [data.verts[tri[0] as usize], data.verts[tri[1] as usize], data.verts[tri[2] as usize]];
+			let face = (v[1].vec3() - v[0].vec3()).cross(v[2].vec3() - v[0].vec3());
+			let normal = Vec3::from(v[0].a_Normal) + Vec3::from(v[1].a_Normal) + Vec3::from(v[2].a_Normal);
+			assert!(face.dot(normal) > 0.0);
+		}
+	}
+}
+
+#[test]
+fn weld_cube() {
+	use geometry::{weld, weld_epsilon};
+	
+	let mut data = ::obj::MeshData::new(data::VERTEX_DATA.to_vec(), (0..36).collect());
+	let report = weld(&mut data);
+	assert_eq!(report.verts_before, 36);
+	assert_eq!(report.verts_after, 24);
+	
+	let mut nudged = data::VERTEX_DATA.to_vec();
+	nudged[5].a_Pos[0] += 0.0001;
+	let mut exact = ::obj::MeshData::new(nudged.clone(), (0..36).collect());
+	assert_eq!(weld(&mut exact).verts_after, 25);
+	let mut data = ::obj::MeshData::new(nudged, (0..36).collect());
+	assert_eq!(weld_epsilon(&mut data, 0.001).verts_after, 24);
+	assert_eq!(data.indices[5], data.indices[0]);
+}
+
+#[test]
+fn optimize_keeps_triangles() {
+	use std::collections::HashSet;
+	use {geometry, shapes};
+	
+	let mut data = shapes::uv_sphere(1.0, 24, 12, Color::white());
+	let triangles = |data: &::obj::MeshData| data.indices.chunks(3).map(|tri| {
+		let mut corners = tri.iter().map(|&i| data.verts[i as usize].a_Pos.iter().map(|f| f.to_bits()).collect::<Vec<_>>()).collect::<Vec<_>>();
+		corners.sort();
+		corners
+	}).collect::<HashSet<_>>();
+	let before = triangles(&data);
+	
+	geometry::optimize(&mut data);
+	assert_eq!(triangles(&data), before);
+	
+	// Vertices are stored in the order they're first used
+	let mut next = 0;
+	for &i in &data.indices {
+		assert!(i <= next);
+		if i == next {
+			next += 1;
+		}
+	}
+	
+	// Point clouds have no indices to follow, and unused vertices are kept
+	let mut points = ::obj::MeshData::new(data.verts[..4].to_vec(), Vec::new());
+	geometry::optimize(&mut points);
+	assert_eq!(points.verts.len(), 4);
+	let mut lines = ::obj::MeshData::new(data.verts[..4].to_vec(), vec![2, 1]);
+	geometry::optimize(&mut lines);
+	assert_eq!((lines.verts.len(), &lines.indices[..]), (4, &[0, 1][..]));
+	assert_eq!(lines.verts[3].a_Pos, data.verts[3].a_Pos);
+}
+
+#[test]
+fn morph_targets_follow_optimize() {
+	use geometry;
+	use obj::MorphTarget;
+	use td::BoundingSphere;
+	
+	// Each offset points back to the origin from its vertex, so it has to stay with it
+	let mut data = ::shapes::uv_sphere(1.0, 12, 6, Color::white());
+	let mut target = MorphTarget {
+		name: "shrink".to_string(),
+		positions: data.verts.iter().map(|v| -v.vec3()).collect(),
+		normals: None,
+	};
+	let sources = geometry::optimize_mapped(&mut data);
+	target.remap(&sources);
+	for (v, p) in data.verts.iter().zip(&target.positions) {
+		assert_eq!(v.vec3() + p, Vector3::zero());
+	}
+	
+	let bounds = BoundingSphere::from_verts(&data.verts);
+	let grown = MorphTarget::bounds(bounds, &[target.clone(), target]);
+	assert!((grown.radius - bounds.radius - 2.0).abs() < 1e-4);
+}
+
+#[test]
+fn dynamic_geometry() {
+	use dynamic::{Geometry, validate, union};
+	use obj::Topology;
+	
+	// Indices have to make up whole primitives of vertices that exist, strips can restart
+	assert!(validate(&[0, 1, 2], 3, Topology::TriangleList).is_ok());
+	assert!(validate(&[0, 1], 3, Topology::TriangleList).is_err());
+	assert!(validate(&[0, 1, 3], 3, Topology::TriangleList).is_err());
+	assert!(validate(&[0, 1, 2, ::std::u32::MAX, 0, 1, 2], 3, Topology::TriangleStrip).is_ok());
+	assert_eq!(union(None, 2..4), 2..4);
+	assert_eq!(union(Some(2..4), 6..8), 2..8);
+	
+	// Updates splice into the data, and the capacity grows geometrically
+	let mut geometry = Geometry::new(vec![0u8, 1, 2], Some(vec![0, 1, 2]), Topology::TriangleList);
+	assert_eq!((geometry.vert_capacity, geometry.index_capacity), (3, 3));
+	assert_eq!(geometry.update_verts(2, &[5, 6, 7]), Ok(2..5));
+	assert_eq!(geometry.verts, vec![0, 1, 5, 6, 7]);
+	assert_eq!(geometry.vert_capacity, 8);
+	assert!(geometry.update_verts(6, &[0]).is_err());
+	assert_eq!(geometry.update_indices(3, &[2, 3, 4]), Ok(3..6));
+	assert!(geometry.update_indices(1, &[0, 1, 2]).is_err());
+	assert!(geometry.update_indices(0, &[0, 1, 5]).is_err());
+	assert_eq!(geometry.indices, Some(vec![0, 1, 2, 2, 3, 4]));
+	assert_eq!(geometry.index_capacity, 8);
+	geometry.reserve(4, 8);
+	assert_eq!((geometry.vert_capacity, geometry.index_capacity), (8, 8));
+	assert!(geometry.truncate(3, 6).is_err());
+	assert!(geometry.truncate(3, 3).is_ok());
+	
+	// Without indices the vertices have to keep making up whole primitives
+	let mut pure = Geometry::new(vec![0u8; 6], None, Topology::TriangleList);
+	assert!(pure.set_verts(vec![0; 4]).is_err());
+	assert!(pure.update_verts(6, &[0, 0]).is_err());
+	assert_eq!(pure.update_verts(3, &[1, 1, 1, 1, 1, 1]), Ok(3..9));
+	assert!(pure.truncate(8, 0).is_err());
+	assert!(pure.truncate(6, 0).is_ok());
+	assert_eq!(pure.index_capacity, 0);
+}
+
+#[test]
+fn simplify_flat_grid() {
+	use {geometry, shapes};
+	
+	// A flat grid can lose all its inner detail without moving the surface
+	let data = shapes::grid(2.0, 2.0, 8, 8, Color::white());
+	let simple = geometry::simplify(&data, 8, 0.001);
+	assert!(simple.indices.len() / 3 <= 8);
+	assert!(simple.verts.iter().all(|v| data.verts.iter().any(|o| o.a_Pos == v.a_Pos)));
+	for v in &simple.verts {
+		assert!(v.a_Pos[0].abs() <= 1.0 && v.a_Pos[1] == 0.0 && v.a_Pos[2].abs() <= 1.0);
+	}
+	
+	let chain = geometry::lod_chain(&data, &[0.5, 0.25], ::std::f32::INFINITY);
+	assert_eq!(chain.len(), 2);
+	assert!(chain[0].indices.len() / 3 <= 64 && chain[1].indices.len() / 3 <= 32);
+	
+	// Data without indices is simplified as a triangle list
+	let listed = ::obj::MeshData::new(data.indices.iter().map(|&i| data.verts[i as usize]).collect(), Vec::new());
+	let simple = geometry::simplify(&listed, 8, 0.001);
+	assert!(!simple.indices.is_empty() && simple.indices.len() / 3 <= 8);
+	let chain = geometry::lod_chain(&listed, &[0.5], ::std::f32::INFINITY);
+	assert!(chain[0].indices.len() / 3 > 32 && chain[0].indices.len() / 3 <= 64);
+	
+	// Unwelded vertices move together, while the sharp edges of a box hold it in shape
+	let mut flat = data.clone();
+	geometry::flat_normals(&mut flat);
+	assert!(geometry::simplify(&flat, 8, 0.001).indices.len() / 3 <= 8);
+	let cube = shapes::cuboid(Vector3::new(1.0, 1.0, 1.0), Color::white());
+	assert_eq!(geometry::simplify(&cube, 0, 0.001).indices.len(), 36);
+}
+
+#[test]
+fn skeleton_clip() {
+	use std::sync::Arc;
+	use math::{Quaternion, Rotation3, Matrix4, SquareMatrix};
+	use skin::{Skeleton, Joint, Trs, SkeletalClip, Channel, Animator, SkinnedVertex};
+	use animation::Curve;
+	
+	let elbow = Trs { translation: Vector3::new(0.0, 1.0, 0.0), ..Trs::identity() };
+	let mut skeleton = Skeleton::new(vec![
+		Joint::new("shoulder", None, Trs::identity()),
+		Joint::new("elbow", Some(0), elbow),
+	]).unwrap();
+	skeleton.bind_rest_pose();
+	assert!(Skeleton::new(vec![Joint::new("orphan", Some(1), Trs::identity())]).is_err());
+	
+	// Joints the shader can't reach are dropped along with their weight
+	let v = SkinnedVertex::new(Vertex::new(0.0, 0.0, 0.0, 1.0, Color::white()), [1, 64, 0, 0], [0.5, 0.5, 0.0, 0.0]);
+	assert_eq!((v.a_Joints, v.a_Weights), ([1, 0, 0, 0], [1.0, 0.0, 0.0, 0.0]));
+	assert_eq!(v.joint_count(), skeleton.joints().len());
+	
+	// At rest every vertex stays where it was modelled
+	for m in skeleton.skinning_matrices(&skeleton.rest_pose()) {
+		let d = m - Matrix4::identity();
+		assert!(d.x.magnitude() + d.y.magnitude() + d.z.magnitude() + d.w.magnitude() < 1e-5);
+	}
+	
+	let bend = Quaternion::from_angle_z(Deg(90.0));
+	let clip = Arc::new(SkeletalClip::new("bend", vec![Channel {
+		joint: 0,
+		translation: None,
+		rotation: Some(Curve::linear(vec![(0.0, Quaternion::from_angle_z(Deg(0.0))), (1.0, bend)])),
+		scale: None,
+	}]));
+	assert_eq!(clip.duration, 1.0);
+	
+	// Halfway through, the elbow has swung 45 degrees around the shoulder
+	let globals = skeleton.globals(&clip.sample(&skeleton, 0.5));
+	let elbow = globals[1].w.truncate();
+	let half = 0.5f32.sqrt();
+	assert!((elbow - Vector3::new(-half, half, 0.0)).magnitude() < 1e-4);
+	
+	// Blending the clip half and half with the rest pose gives the same angle at its end
+	let mut animator = Animator::new();
+	animator.play(clip.clone(), 1.0);
+	let rest = animator.play(Arc::new(SkeletalClip::new("rest", vec![])), 1.0);
+	animator.layers[rest].looping = false;
+	animator.layers[0].looping = false;
+	animator.advance(1000.0);
+	let globals = skeleton.globals(&animator.pose(&skeleton));
+	assert!((globals[1].w.truncate() - Vector3::new(-half, half, 0.0)).magnitude() < 1e-4);
+}
+
+#[test]
+fn keyframe_curves() {
+	use std::sync::Arc;
+	use animation::{Curve, Easing, Track, Clip, Animations};
+	use view::{View, Rect};
+	
+	let keys = vec![(1.0, 10.0), (0.0, 0.0), (2.0, 0.0)];
+	let linear = Curve::linear(keys.clone());
+	assert_eq!(linear.sample(-1.0), Some(0.0));
+	assert_eq!(linear.sample(0.5), Some(5.0));
+	assert_eq!(linear.sample(1.5), Some(5.0));
+	assert_eq!(Curve::step(keys.clone()).sample(0.9), Some(0.0));
+	assert_eq!(Curve::linear(keys.clone()).with_easing(Easing::QuadIn).sample(0.5), Some(2.5));
+	// The spline passes through every key and rounds off the peak between them
+	let cubic = Curve::cubic(keys);
+	assert!((cubic.sample(1.0).unwrap() - 10.0).abs() < 1e-5);
+	assert!(cubic.sample(0.5).unwrap() > 5.0);
+	// Keys spaced unevenly along a straight line stay on it
+	let uneven = Curve::cubic(vec![(0.0, 0.0), (1.0, 1.0), (10.0, 10.0)]);
+	assert!((uneven.sample(0.5).unwrap() - 0.5).abs() < 1e-5 && (uneven.sample(4.0).unwrap() - 4.0).abs() < 1e-4);
+	
+	let clip = Arc::new(Clip::new("pan", vec![
+		Track::CameraPosition(None, Curve::linear(vec![(0.0, Vector3::zero()), (2.0, Vector3::new(4.0, 0.0, 0.0))])),
+		Track::CameraPosition(Some(0), Curve::linear(vec![(0.0, Vector3::zero()), (2.0, Vector3::new(0.0, 4.0, 0.0))])),
+		Track::LightAmbient(Curve::linear(vec![(0.0, 0.0), (1.0, 1.0)])),
+	]));
+	assert_eq!(clip.duration, 2.0);
+	
+	let mut camera = Camera::new(PerspectiveFov { fovy: Deg(45.0).into(), aspect: 1.0, near: 0.1, far: 100.0 });
+	let mut views = vec![View::new(Camera::new(PerspectiveFov { fovy: Deg(45.0).into(), aspect: 1.0, near: 0.1, far: 100.0 }), Rect::full())];
+	let mut light = Light::default();
+	let mut animations = Animations::new();
+	animations.play_looping("pan", clip).speed = 2.0;
+	animations.advance(750.0);
+	animations.apply(&mut HashMap::new(), &mut camera, &mut views, &mut light);
+	assert!((camera.get_pos() - Vector3::new(3.0, 0.0, 0.0)).magnitude() < 1e-5);
+	assert!((views[0].camera.get_pos() - Vector3::new(0.0, 3.0, 0.0)).magnitude() < 1e-5);
+	assert_eq!(light.ambient, 1.0);
+	
+	// Looping wraps back around to the start
+	animations.advance(500.0);
+	animations.apply(&mut HashMap::new(), &mut camera, &mut views, &mut light);
+	assert!((camera.get_pos() - Vector3::new(1.0, 0.0, 0.0)).magnitude() < 1e-5);
+	assert!(animations.is_playing("pan"));
+	
+	// Clips animating the same property are applied in order of their names
+	animations.play("zz", Arc::new(Clip::new("dim", vec![Track::LightAmbient(Curve::step(vec![(0.0, 0.25)]))])));
+	animations.play("aa", Arc::new(Clip::new("bright", vec![Track::LightAmbient(Curve::step(vec![(0.0, 0.75)]))])));
+	animations.apply(&mut HashMap::new(), &mut camera, &mut views, &mut light);
+	assert_eq!(light.ambient, 0.25);
+}
+
+#[test]
+fn orbit_controller() {
+	use control::OrbitController;
+	
+	let mut camera = Camera::new(PerspectiveFov { fovy: Deg(45.0).into(), aspect: 1.0, near: 0.1, far: 100.0 });
+	let target = Vector3::new(1.0, 2.0, 3.0);
+	let mut orbit = OrbitController::new(target, 5.0);
+	orbit.damping = 0.0;
+	orbit.update(16.0, &mut camera);
+	assert!((camera.get_pos() - Vector3::new(1.0, 2.0, 8.0)).magnitude() < 1e-4);
+	assert!((camera.get_vec().0 - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-4);
+	
+	// Pitch and distance stay within their limits, and the camera keeps facing the target
+	orbit.rotate(90.0, 200.0);
+	orbit.zoom(-1000.0);
+	assert_eq!(orbit.pitch, orbit.max_pitch);
+	assert_eq!(orbit.distance, orbit.max_distance);
+	orbit.distance = 5.0;
+	orbit.update(16.0, &mut camera);
+	let to_target = (target - camera.get_pos()).normalize();
+	assert!((camera.get_vec().0 - to_target).magnitude() < 1e-4);
+	
+	// Starting from a camera keeps it where it is
+	let copy = OrbitController::from_camera(&camera, target);
+	assert!((copy.distance - 5.0).abs() < 1e-4);
+	assert!((copy.pitch - orbit.pitch).abs() < 1e-3);
+}
+
+#[test]
+fn fly_controller() {
+	use control::FlyController;
+	use window::VirtualKeyCode;
+	
+	let mut camera = Camera::new(PerspectiveFov { fovy: Deg(45.0).into(), aspect: 1.0, near: 0.1, far: 100.0 });
+	let mut fly = FlyController::new(2.0);
+	fly.acceleration = 0.0;
+	
+	// Looking up doesn't lift a level walk off the ground, and pitch stops short of straight up
+	fly.look(0.0, 120.0);
+	fly.set_key(VirtualKeyCode::W, true);
+	fly.update(500.0, &mut camera);
+	assert_eq!(fly.pitch, 89.99);
+	assert!((camera.get_vec().0.y - 89.99f32.to_radians().sin()).abs() < 1e-6);
+	assert!((camera.get_pos() - Vector3::new(1.0, 0.0, 0.0)).magnitude() < 1e-4);
+	
+	// The same time split into more frames covers the same distance
+	fly.set_key(VirtualKeyCode::LShift, true);
+	for _ in 0..10 {
+		fly.update(50.0, &mut camera);
+	}
+	assert!((camera.get_pos() - Vector3::new(4.0, 0.0, 0.0)).magnitude() < 1e-4);
+	
+	fly.set_key(VirtualKeyCode::W, false);
+	fly.update(100.0, &mut camera);
+	assert_eq!(fly.velocity(), Vector3::zero());
+}
+
+#[test]
+fn orthographic_projection() {
+	use math::{Matrix4, Vector4};
+	
+	let mut camera = Camera::new(Projection::Orthographic { height: 4.0, aspect: 1.0, near: 0.1, far: 100.0 });
+	camera.proj.set_aspect(2.0);
+	let corner = camera.proj.matrix() * Vector4::new(4.0, 2.0, -50.0, 1.0);
+	assert!((corner.x - 1.0).abs() < 1e-5 && (corner.y - 1.0).abs() < 1e-5);
+	
+	// Size on screen doesn't depend on distance
+	let sphere = BoundingSphere { center: Vector3::new(100.0, 0.0, 0.0), radius: 1.0 };
+	assert!((camera.projected_size(&sphere) - 0.5).abs() < 1e-5);
+	assert!(camera.proj.is_orthographic());
+	
+	camera.proj = Projection::Custom(Matrix4::from_scale(2.0));
+	camera.proj.set_aspect(0.5);
+	assert_eq!(camera.proj.matrix(), Matrix4::from_scale(2.0));
+	assert_eq!(camera.proj.aspect(), None);
+}
+
+#[test]
+fn infinite_perspective() {
+	use math::{Vector4, Matrix4};
+	
+	let fovy = Deg(60.0).into();
+	let infinite = Projection::Perspective(PerspectiveFov { fovy, aspect: 1.5, near: 0.5, far: std::f32::INFINITY }).matrix();
+	let finite = Matrix4::from(PerspectiveFov { fovy, aspect: 1.5, near: 0.5, far: 1e7 });
+	let near = infinite * Vector4::new(0.0, 0.0, -0.5, 1.0);
+	assert!((near.z / near.w + 1.0).abs() < 1e-5);
+	for &z in &[-1.0, -10.0, -1000.0] {
+		let (a, b) = (infinite * Vector4::new(1.0, 1.0, z, 1.0), finite * Vector4::new(1.0, 1.0, z, 1.0));
+		assert!((a / a.w - b / b.w).magnitude() < 1e-4);
+	}
+}
+
+#[test]
+fn camera_orientation() {
+	let mut camera = Camera::new(PerspectiveFov { fovy: Deg(45.0).into(), aspect: 1.0, near: 0.1, far: 100.0 });
+	
+	// Euler angles survive a round trip, roll included
+	camera.set_rot(Euler::new(Deg(30.0), Deg(-120.0), Deg(45.0)));
+	let rot = camera.get_rot();
+	assert!((rot.x.0 - 30.0).abs() < 1e-3 && (rot.y.0 + 120.0).abs() < 1e-3 && (rot.z.0 - 45.0).abs() < 1e-3);
+	
+	// Rolling clockwise leans the top of the camera to the right
+	camera.set_rot(Euler::new(Deg(0.0), Deg(0.0), Deg(0.0)));
+	camera.roll(Deg(90.0));
+	let (front, _, up) = camera.get_vec();
+	assert!((front - Vector3::new(1.0, 0.0, 0.0)).magnitude() < 1e-5);
+	assert!((up - Vector3::new(0.0, 0.0, 1.0)).magnitude() < 1e-5);
+	assert!((camera.get_rot().z.0 - 90.0).abs() < 1e-3);
+	
+	// With z up, looking at something keeps z at the top of the view
+	camera.set_world_up(Vector3::new(0.0, 0.0, 1.0));
+	camera.set_pos(Vector3::new(0.0, -5.0, 5.0));
+	camera.look_at(Vector3::zero());
+	let (front, right, up) = camera.get_vec();
+	assert!((front - Vector3::new(0.0, 1.0, -1.0).normalize()).magnitude() < 1e-5);
+	assert!(right.z.abs() < 1e-5 && up.z > 0.0);
+	assert!(camera.get_rot().z.0.abs() < 1e-3);
+	assert!((camera.get_rot().x.0 + 45.0).abs() < 1e-3);
+	
+	camera.rotate_around_axis(Vector3::new(0.0, 0.0, 1.0), Deg(180.0));
+	assert!((camera.get_vec().0 - Vector3::new(0.0, -1.0, -1.0).normalize()).magnitude() < 1e-5);
+}
+
+#[test]
+fn camera_screen_helpers() {
+	let size = [800.0, 600.0];
+	let mut camera = Camera::new(PerspectiveFov { fovy: Deg(60.0).into(), aspect: 800.0 / 600.0, near: 0.1, far: 100.0 });
+	camera.set_pos(Vector3::new(1.0, 2.0, 3.0));
+	camera.set_rot(Euler::new(Deg(-20.0), Deg(35.0), Deg(10.0)));
+	
+	let point = camera.get_pos() + camera.get_vec().0 * 10.0 + camera.get_vec().1 * 2.0;
+	let screen = camera.world_to_screen(point, size).unwrap();
+	assert!(screen.x > 400.0 && screen.z > 0.0 && screen.z < 1.0);
+	let back = camera.unproject(screen.x, screen.y, screen.z, size).unwrap();
+	assert!((back - point).magnitude() < 1e-2);
+	assert!(camera.world_to_screen(camera.get_pos() - camera.get_vec().0, size).is_none());
+	
+	// The ray under the middle of the screen is the way the camera faces
+	let ray = camera.screen_to_world_ray(400.0, 300.0, size).unwrap();
+	assert!((ray.direction - camera.get_vec().0).magnitude() < 1e-3);
+	assert!((ray.origin - camera.get_pos()).magnitude() < 0.2);
+	
+	let frustum = camera.frustum();
+	assert!(frustum.contains_point(point));
+	assert!(!frustum.contains_point(camera.get_pos() - camera.get_vec().0));
+	assert!(!frustum.contains_point(camera.get_pos() + camera.get_vec().0 * 200.0));
+	
+	// Every corner of a framed box ends up on screen
+	let bounds = Aabb { min: Vector3::new(-3.0, -1.0, -2.0), max: Vector3::new(5.0, 1.0, 0.0) };
+	camera.frame_bounds(&bounds);
+	for i in 0..8 {
+		let corner = Vector3::new(
+			if i & 1 == 0 { bounds.min.x } else { bounds.max.x },
+			if i & 2 == 0 { bounds.min.y } else { bounds.max.y },
+			if i & 4 == 0 { bounds.min.z } else { bounds.max.z },
+		);
+		let s = camera.world_to_screen(corner, size).unwrap();
+		assert!(s.x >= 0.0 && s.x <= size[0] && s.y >= 0.0 && s.y <= size[1]);
+		assert!(camera.frustum().contains_point(corner));
+	}
+}
+
+#[test]
+fn view_rects() {
+	use view::Rect;
+	
+	// The bottom right of a four-way layout
+	let rect = Rect::grid(2, 2, 1, 1);
+	assert_eq!(rect, Rect::new(0.5, 0.5, 0.5, 0.5));
+	assert_eq!(rect.pixels([800, 600]), ([400.0, 300.0], [400.0, 300.0]));
+	
+	// Side by side halves of a wide window are taller than they are wide
+	let left = Rect::grid(2, 1, 0, 0);
+	assert!((left.aspect([1000, 800]) - 500.0 / 800.0).abs() < 1e-6);
+}
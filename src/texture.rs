@@ -0,0 +1,70 @@
+use std::sync::Arc;
+use std::fmt;
+
+use vulkano::image::{ImmutableImage, ImageViewAccess, Dimensions};
+use vulkano::format::{R8G8B8A8Srgb, R8G8B8A8Unorm};
+use vulkano::sampler::{Sampler, Filter, MipmapMode, SamplerAddressMode};
+use vulkano::device::{Device, Queue};
+
+use RenderInternal;
+
+pub struct Texture {
+	pub(crate) image: Arc<ImageViewAccess + Send + Sync>,
+	pub(crate) sampler: Arc<Sampler>,
+	dimensions: [u32; 2],
+}
+
+impl fmt::Debug for Texture {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Texture {{ dimensions: {:?} }}", self.dimensions)
+	}
+}
+
+impl Texture {
+	/// Uploads tightly packed 8-bit sRGB RGBA pixels, row by row from the top left
+	pub fn from_rgba(internal: &RenderInternal, width: u32, height: u32, pixels: Vec<u8>) -> Result<Self, ()> {
+		Texture::upload(&internal.device, &internal.queue, width, height, pixels, true)
+	}
+	
+	/// Like `from_rgba`, but the pixels are used as-is instead of being decoded from sRGB. This is
+	/// needed for data such as normal maps.
+	pub fn from_rgba_linear(internal: &RenderInternal, width: u32, height: u32, pixels: Vec<u8>) -> Result<Self, ()> {
+		Texture::upload(&internal.device, &internal.queue, width, height, pixels, false)
+	}
+	
+	pub(crate) fn upload(device: &Arc<Device>, queue: &Arc<Queue>, width: u32, height: u32, pixels: Vec<u8>, srgb: bool) -> Result<Self, ()> {
+		if width == 0 || height == 0 || pixels.len() != width as usize * height as usize * 4 {
+			return Err(())
+		}
+		
+		let dimensions = Dimensions::Dim2d { width, height };
+		let image: Arc<ImageViewAccess + Send + Sync> = if srgb {
+			ImmutableImage::from_iter(pixels.into_iter(), dimensions, R8G8B8A8Srgb, queue.clone()).map_err(|_| ())?.0
+		} else {
+			ImmutableImage::from_iter(pixels.into_iter(), dimensions, R8G8B8A8Unorm, queue.clone()).map_err(|_| ())?.0
+		};
+		
+		Ok(Texture {
+			image,
+			sampler: Sampler::simple_repeat_linear(device.clone()),
+			dimensions: [width, height],
+		})
+	}
+	
+	/// Samples an image the renderer draws into, clamped at its edges
+	pub(crate) fn from_image(device: &Arc<Device>, image: Arc<ImageViewAccess + Send + Sync>, dimensions: [u32; 2]) -> Self {
+		let sampler = Sampler::new(device.clone(), Filter::Linear, Filter::Linear, MipmapMode::Nearest,
+			SamplerAddressMode::ClampToEdge, SamplerAddressMode::ClampToEdge, SamplerAddressMode::ClampToEdge,
+			0.0, 1.0, 0.0, 0.0).unwrap();
+		Texture { image, sampler, dimensions }
+	}
+	
+	/// A 1x1 texture of a single color
+	pub fn solid(internal: &RenderInternal, pixel: [u8; 4]) -> Self {
+		Texture::from_rgba(internal, 1, 1, pixel.to_vec()).unwrap()
+	}
+	
+	pub fn dimensions(&self) -> [u32; 2] {
+		self.dimensions
+	}
+}
@@ -0,0 +1,87 @@
+//! Views draw the scene from their own camera into part of the window, for split-screen, multiple
+//! viewports and picture-in-picture.
+
+use td::{Camera, Color};
+
+/// A part of the window, as fractions of its size from its top left corner, so it keeps its place
+/// when the window is resized
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rect {
+	pub x: f32,
+	pub y: f32,
+	pub width: f32,
+	pub height: f32,
+}
+
+impl Rect {
+	pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+		Rect { x, y, width, height }
+	}
+	
+	/// The whole window
+	pub fn full() -> Self {
+		Rect::new(0.0, 0.0, 1.0, 1.0)
+	}
+	
+	/// One cell of the window split evenly into `columns` by `rows`, such as 2 by 2 for a
+	/// four-way CAD layout or 2 by 1 for side by side split-screen
+	pub fn grid(columns: u32, rows: u32, column: u32, row: u32) -> Self {
+		let (width, height) = (1.0 / columns.max(1) as f32, 1.0 / rows.max(1) as f32);
+		Rect::new(column as f32 * width, row as f32 * height, width, height)
+	}
+	
+	/// The rectangle in pixels as `[x, y]` and `[width, height]`, within a target `size` pixels big
+	pub fn pixels(&self, size: [u32; 2]) -> ([f32; 2], [f32; 2]) {
+		let (w, h) = (size[0] as f32, size[1] as f32);
+		([self.x * w, self.y * h], [self.width * w, self.height * h])
+	}
+	
+	/// Width divided by height within a target `size` pixels big
+	pub fn aspect(&self, size: [u32; 2]) -> f32 {
+		let (_, pixels) = self.pixels(size);
+		if pixels[1] > 0.0 { pixels[0] / pixels[1] } else { 1.0 }
+	}
+}
+
+impl Default for Rect {
+	fn default() -> Self {
+		Rect::full()
+	}
+}
+
+/// What is wiped from a view's rectangle before it's drawn
+#[derive(Copy, Clone, Debug)]
+pub struct Clear {
+	/// Fills the rectangle with a color, or leaves what earlier views drew in it if `None`
+	pub color: Option<Color>,
+	/// Lets the view draw over anything earlier views drew in its rectangle
+	pub depth: bool,
+}
+
+impl Default for Clear {
+	fn default() -> Self {
+		Clear { color: Some(Color::black()), depth: true }
+	}
+}
+
+/// One camera's view of the scene. Views are drawn in order, so later ones end up on top where
+/// they overlap.
+pub struct View {
+	pub camera: Camera,
+	pub rect: Rect,
+	pub clear: Clear,
+	/// Only objects with a layer in this mask are drawn
+	pub layers: u32,
+}
+
+impl View {
+	/// A view that clears its rectangle and draws every object
+	pub fn new(camera: Camera, rect: Rect) -> Self {
+		View {
+			camera,
+			rect,
+			clear: Clear::default(),
+			layers: !0,
+		}
+	}
+}